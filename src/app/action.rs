@@ -1,9 +1,14 @@
 use crate::{
+    DATA,
     app::{TEXT_FG_COLOR, menu::Menu},
     preset::Preset,
+    transfer::OnConflict,
 };
 use ratatui::{text::Line, widgets::ListItem};
-use std::sync::{Arc, Mutex};
+use std::{
+    path::PathBuf,
+    sync::{Arc, Mutex},
+};
 
 pub struct Action {
     pub name: String,
@@ -25,10 +30,23 @@ impl Action {
 
 #[derive(Clone)]
 pub enum ActionType {
+    /// Applies a preset then asks to keep it, reverting to the previous settings after a countdown
     ApplyPreset(Arc<Mutex<Preset>>),
+    /// Displays what applying a preset would change, without changing anything
+    DryRunPreset(Arc<Mutex<Preset>>),
     DeletePreset(Arc<Mutex<Preset>>),
-    /// Retrieves the current screen layout and saves it as a preset
-    SaveCurrentConfigAsPreset,
+    /// Shows the problems of the layout of a preset
+    CheckPreset(Arc<Mutex<Preset>>),
+    /// Snaps the monitors of a preset together, pushes overlapping ones apart and moves the primary one to 0,0
+    NormalizePreset(Arc<Mutex<Preset>>),
+    /// Switches to the next policy for the monitors of a preset that are not connected
+    CycleMissingMonitorPolicy(Arc<Mutex<Preset>>),
+    /// Retrieves the current screen layout, saves it as a preset with the given name and opens its menu
+    SaveCurrentConfigAsPreset(String),
+    /// Adds the presets of a file, see [`crate::transfer::import`]
+    ImportPresets(PathBuf, OnConflict),
+    /// Displays a text input the user can type in
+    TextInput(TextInput),
     /// Displays a text input the user can type in
     ChangeHotkeyInput(Arc<Mutex<Preset>>),
     /// Displays a dismmisable message with content and reloads the current menu's actions
@@ -43,6 +61,59 @@ pub enum ActionType {
     ToggleStartup,
 }
 
+/// What the text typed in a text input is for
+#[derive(Clone)]
+pub enum TextInput {
+    /// Name of the preset about to be saved from the current layout
+    NewPresetName,
+    Rename(Arc<Mutex<Preset>>),
+    /// An empty description removes it
+    Describe(Arc<Mutex<Preset>>),
+    /// File to export the preset, or every preset, to
+    Export(Option<Arc<Mutex<Preset>>>),
+    /// File to import presets from
+    Import,
+}
+
+impl TextInput {
+    pub fn title(&self) -> String {
+        match self {
+            Self::NewPresetName => String::from("Name of the new preset"),
+            Self::Rename(preset) => format!("Renaming {}", preset.lock().unwrap()),
+            Self::Describe(preset) => format!("Description of {}", preset.lock().unwrap()),
+            Self::Export(Some(preset)) => format!("File to export {} to", preset.lock().unwrap()),
+            Self::Export(None) => String::from("File to export every preset to"),
+            Self::Import => String::from("File to import presets from"),
+        }
+    }
+
+    /// Text the input starts with
+    pub fn initial_text(&self) -> String {
+        match self {
+            Self::NewPresetName => {
+                let names: Vec<String> = DATA
+                    .lock()
+                    .unwrap()
+                    .presets
+                    .iter()
+                    .map(|preset| preset.lock().unwrap().name.clone())
+                    .collect();
+                Preset::default_name(names.iter().map(String::as_str))
+            }
+            Self::Rename(preset) => preset.lock().unwrap().name.clone(),
+            Self::Describe(preset) => preset
+                .lock()
+                .unwrap()
+                .description
+                .clone()
+                .unwrap_or_default(),
+            Self::Export(Some(preset)) => format!("{}.json", preset.lock().unwrap()),
+            Self::Export(None) => String::from("presets.json"),
+            Self::Import => String::new(),
+        }
+    }
+}
+
 impl From<&Action> for ListItem<'_> {
     fn from(value: &Action) -> Self {
         let line = Line::styled(value.name.clone(), TEXT_FG_COLOR);
@@ -0,0 +1,39 @@
+use crate::{
+    app::{
+        action::{Action, ActionType},
+        menu::Menu,
+    },
+    transfer::OnConflict,
+};
+use std::path::PathBuf;
+
+/// Asks what to do with the imported presets named like existing ones
+#[derive(Clone)]
+pub struct MenuImport {
+    pub path: PathBuf,
+    /// Names of the presets both imported and existing
+    pub conflicts: Vec<String>,
+}
+
+impl Menu for MenuImport {
+    fn name(&self) -> String {
+        format!("Presets named {} exist already", self.conflicts.join(", "))
+    }
+
+    fn with_actions(&self) -> Vec<Action> {
+        [
+            ("↻ Import them under another name", OnConflict::Rename),
+            ("⇄ Replace the existing ones", OnConflict::Replace),
+            ("→ Skip them", OnConflict::Skip),
+        ]
+        .into_iter()
+        .map(|(name, on_conflict)| Action {
+            name: String::from(name),
+            action_type: vec![
+                ActionType::GoBack,
+                ActionType::ImportPresets(self.path.clone(), on_conflict),
+            ],
+        })
+        .collect()
+    }
+}
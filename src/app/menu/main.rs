@@ -1,8 +1,8 @@
 use crate::{
     DATA,
     app::{
-        action::{Action, ActionType},
-        menu::{Menu, manage_preset::MenuManagePreset, preset_list::MenuPresetList},
+        action::{Action, ActionType, TextInput},
+        menu::{Menu, preset_list::MenuPresetList},
     },
 };
 
@@ -24,21 +24,24 @@ impl Menu for MenuMain {
                 "≡ Manage presets",
                 ActionType::OpenMenu(Box::new(MenuPresetList)),
             ),
+            Action::single_action(
+                "▭ Connected monitors",
+                ActionType::DisplayMessage(
+                    DATA.lock()
+                        .unwrap()
+                        .monitors
+                        .iter()
+                        .map(|monitor| monitor.to_string())
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                ),
+            ),
+            Action::single_action(
+                "↓ Save current config as preset",
+                ActionType::TextInput(TextInput::NewPresetName),
+            ),
             Action {
-                name: String::from("↓ Save current config as preset"),
-                action_type: vec![
-                    ActionType::SaveCurrentConfigAsPreset,
-                    ActionType::DisplayMessage(format!(
-                        "Current preset successfully saved as {}",
-                        DATA.lock().unwrap().presets.last().unwrap().lock().unwrap()
-                    )),
-                    ActionType::OpenMenu(Box::new(MenuManagePreset {
-                        preset: DATA.lock().unwrap().presets.last().unwrap().clone(),
-                    })),
-                ],
-            },
-            Action {
-                name: String::from("⇄ Toggle open on windows startup"),
+                name: String::from("⇄ Toggle open on startup"),
                 action_type: vec![ActionType::ToggleStartup],
             },
         ]
@@ -1,6 +1,6 @@
 use crate::{
     app::{
-        action::{Action, ActionType},
+        action::{Action, ActionType, TextInput},
         menu::Menu,
     },
     preset::Preset,
@@ -14,25 +14,50 @@ pub struct MenuManagePreset {
 
 impl Menu for MenuManagePreset {
     fn name(&self) -> String {
-        String::from(format!("Editing {} ...", self.preset.lock().unwrap()))
+        let preset = self.preset.lock().unwrap();
+        match &preset.description {
+            Some(description) => format!("Editing {} ({}) ...", preset, description),
+            None => format!("Editing {} ...", preset),
+        }
     }
 
     fn with_actions(&self) -> Vec<Action> {
+        let layout = self.preset.lock().unwrap().layout();
         vec![
-            Action {
-                name: String::from("✓ Apply"),
-                action_type: vec![
-                    ActionType::ApplyPreset(self.preset.clone()),
-                    ActionType::DisplayMessage(format!(
-                        "Preset {} successfully applied",
-                        self.preset.lock().unwrap()
-                    )),
-                ],
-            },
+            Action::single_action("✓ Apply", ActionType::ApplyPreset(self.preset.clone())),
+            Action::single_action("≡ Show settings", ActionType::DisplayMessage(layout)),
+            Action::single_action("? Dry run", ActionType::DryRunPreset(self.preset.clone())),
+            Action::single_action(
+                "⊞ Check layout",
+                ActionType::CheckPreset(self.preset.clone()),
+            ),
+            Action::single_action(
+                "⊹ Normalise layout",
+                ActionType::NormalizePreset(self.preset.clone()),
+            ),
+            Action::single_action(
+                "✎ Rename",
+                ActionType::TextInput(TextInput::Rename(self.preset.clone())),
+            ),
+            Action::single_action(
+                "✎ Edit description",
+                ActionType::TextInput(TextInput::Describe(self.preset.clone())),
+            ),
             Action {
                 name: String::from("⌨ Edit keyboard shortcut"),
                 action_type: vec![ActionType::ChangeHotkeyInput(self.preset.clone())],
             },
+            Action::single_action(
+                format!(
+                    "⚠ Missing monitors: {}",
+                    self.preset.lock().unwrap().on_missing_monitor
+                ),
+                ActionType::CycleMissingMonitorPolicy(self.preset.clone()),
+            ),
+            Action::single_action(
+                "⇪ Export",
+                ActionType::TextInput(TextInput::Export(Some(self.preset.clone()))),
+            ),
             Action {
                 name: String::from("× Delete"),
                 action_type: vec![
@@ -1,3 +1,4 @@
+pub mod import;
 pub mod main;
 pub mod manage_preset;
 mod preset_list;
@@ -65,7 +66,7 @@ impl Widget for &mut RenderedMenu {
 }
 
 const fn alternate_colors(i: usize) -> Color {
-    if i % 2 == 0 {
+    if i.is_multiple_of(2) {
         NORMAL_ROW_BG
     } else {
         ALT_ROW_BG_COLOR
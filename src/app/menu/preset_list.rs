@@ -1,7 +1,7 @@
 use crate::{
     DATA,
     app::{
-        action::{Action, ActionType},
+        action::{Action, ActionType, TextInput},
         menu::{Menu, manage_preset::MenuManagePreset},
     },
 };
@@ -15,14 +15,25 @@ impl Menu for MenuPresetList {
     }
 
     fn with_actions(&self) -> Vec<Action> {
-        let mut vec: Vec<Action> = DATA
-            .lock()
-            .unwrap()
+        let data = DATA.lock().unwrap();
+        let mut vec: Vec<Action> = data
             .presets
             .iter()
             .map(|preset| {
+                let name = {
+                    let preset = preset.lock().unwrap();
+                    if preset
+                        .is_applied(&data.monitors)
+                        .is_ok_and(|applied| applied)
+                    {
+                        format!("{} (already applied)", preset)
+                    } else {
+                        preset.to_string()
+                    }
+                };
+
                 Action::single_action(
-                    preset.lock().unwrap().to_string(),
+                    name,
                     ActionType::OpenMenu(Box::new(MenuManagePreset {
                         preset: preset.clone(),
                     })),
@@ -30,6 +41,16 @@ impl Menu for MenuPresetList {
             })
             .collect();
         vec.sort_by(|a, b| a.name.cmp(&b.name));
+        vec.push(Action::single_action(
+            "⇩ Import presets",
+            ActionType::TextInput(TextInput::Import),
+        ));
+        if !data.presets.is_empty() {
+            vec.push(Action::single_action(
+                "⇪ Export every preset",
+                ActionType::TextInput(TextInput::Export(None)),
+            ));
+        }
         vec
     }
 }
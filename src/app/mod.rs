@@ -2,14 +2,22 @@ mod action;
 mod input;
 mod menu;
 
-use crate::DATA;
+#[cfg(not(windows))]
+use crate::app::input::keybind_from_key_event;
+#[cfg(windows)]
 use crate::app::input::{get_handle, read_input, set_console_to_input_mode};
 use crate::app::menu::Menu;
 use crate::app::{
-    action::ActionType,
-    menu::{RenderedMenu, main::MenuMain},
+    action::{ActionType, TextInput},
+    menu::{RenderedMenu, import::MenuImport, main::MenuMain, manage_preset::MenuManagePreset},
 };
-use crate::preset::{Keybind, Preset};
+#[cfg(not(windows))]
+use crate::error::ThisError;
+use crate::layout::Diagnostic;
+use crate::monitor::Monitor;
+use crate::preset::{ApplyOutcome, Keybind, Preset};
+use crate::transfer::{self, OnConflict};
+use crate::{DATA, config, confirm};
 use color_eyre::eyre::Result;
 use ratatui::layout::Alignment;
 use ratatui::style::palette::tailwind::{EMERALD, ZINC};
@@ -23,15 +31,25 @@ use ratatui::{
     widgets::{Paragraph, Widget},
 };
 use std::fs;
+use std::path::PathBuf;
 use std::process::{Command, Stdio};
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+#[cfg(windows)]
 use windows::Win32::Foundation::HANDLE;
+#[cfg(windows)]
 use windows::Win32::System::Console::CONSOLE_MODE;
 
 pub struct App {
+    #[cfg(windows)]
     console_mode: CONSOLE_MODE,
+    #[cfg(windows)]
     console_handle: HANDLE,
     rendered_change_hotkey: Option<(Arc<Mutex<Preset>>, Option<Keybind>)>,
+    /// What the text is for and the text typed so far
+    rendered_text_input: Option<(TextInput, String)>,
+    /// Message shown once the preset just applied is kept, the settings it replaced and when they are restored
+    rendered_keep_settings: Option<(String, Preset, Instant)>,
     rendered_message: Option<String>,
     should_exit: bool,
     /// A screen stack
@@ -41,9 +59,13 @@ pub struct App {
 impl Default for App {
     fn default() -> Self {
         Self {
+            #[cfg(windows)]
             console_mode: CONSOLE_MODE(0),
+            #[cfg(windows)]
             console_handle: get_handle().unwrap(),
             rendered_change_hotkey: None,
+            rendered_text_input: None,
+            rendered_keep_settings: None,
             rendered_message: None,
             should_exit: false,
             path: vec![(Box::new(MenuMain) as Box<dyn Menu>).into()],
@@ -60,10 +82,42 @@ const TEXT_FG_COLOR: Color = ZINC.c50;
 
 impl App {
     pub const HEADLESS_ARG: &'static str = "--headless";
+    /// Prints what applying every preset would change instead of starting the app
+    pub const DRY_RUN_ARG: &'static str = "--dry-run";
+    /// Prints the problems of the layout of every preset instead of starting the app
+    pub const CHECK_ARG: &'static str = "--check";
+    /// Followed by the presets file to use, see [`crate::config::path`]
+    pub const CONFIG_ARG: &'static str = "--config";
+    /// Followed by a file to write the presets to, in the format of its extension, instead of starting the app
+    pub const CONVERT_ARG: &'static str = "--convert";
+    /// Followed by a file to write presets to, and the names of the presets, every preset without any
+    pub const EXPORT_ARG: &'static str = "--export";
+    /// Followed by a file to add the presets of, see [`crate::transfer::import`]
+    pub const IMPORT_ARG: &'static str = "--import";
+    /// Followed by what importing does with presets named like existing ones, see [`crate::transfer::OnConflict`]
+    pub const ON_CONFLICT_ARG: &'static str = "--on-conflict";
 
     pub fn run(mut self, terminal: &mut DefaultTerminal) -> Result<()> {
         while !self.should_exit {
             terminal.draw(|frame| frame.render_widget(&mut self, frame.area()))?;
+
+            // Redraws the countdown every second, keys pressed meanwhile don't hold it back
+            if let Some(deadline) = self
+                .rendered_keep_settings
+                .as_ref()
+                .map(|(_, _, deadline)| *deadline)
+            {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                let pressed = event::poll(remaining.min(Duration::from_secs(1)))?;
+                if Instant::now() >= deadline {
+                    self.keep_settings(false)?;
+                    continue;
+                }
+                if !pressed {
+                    continue;
+                }
+            }
+
             if let Some(key) = event::read()?.as_key_press_event() {
                 self.handle_key(key)?;
             }
@@ -77,6 +131,16 @@ impl App {
             return Ok(());
         }
 
+        if self.rendered_keep_settings.is_some() {
+            match key.code {
+                KeyCode::Enter | KeyCode::Char('y') => self.keep_settings(true)?,
+                KeyCode::Esc | KeyCode::Char('n') => self.keep_settings(false)?,
+                _ => {}
+            }
+
+            return Ok(());
+        }
+
         if let Some((preset, input)) = &mut self.rendered_change_hotkey {
             match key.code {
                 KeyCode::Delete => {
@@ -101,7 +165,12 @@ impl App {
                     self.go_back();
                 }
                 _other => {
-                    if let Some(keybind) = read_input(self.console_handle).unwrap() {
+                    #[cfg(windows)]
+                    let keybind = read_input(self.console_handle).unwrap();
+                    #[cfg(not(windows))]
+                    let keybind = keybind_from_key_event(key);
+
+                    if let Some(keybind) = keybind {
                         *input = Some(keybind)
                     }
                 }
@@ -110,6 +179,24 @@ impl App {
             return Ok(());
         }
 
+        if let Some((_, text)) = &mut self.rendered_text_input {
+            match key.code {
+                KeyCode::Enter => {
+                    if let Some((input, text)) = self.rendered_text_input.take() {
+                        self.submit_text_input(input, text.trim().to_string())?;
+                    }
+                }
+                KeyCode::Esc => self.rendered_text_input = None,
+                KeyCode::Backspace => {
+                    text.pop();
+                }
+                KeyCode::Char(c) => text.push(c),
+                _ => {}
+            }
+
+            return Ok(());
+        }
+
         match key.code {
             KeyCode::Char('q') | KeyCode::Esc => {
                 self.should_exit = true;
@@ -132,6 +219,98 @@ impl App {
         }
     }
 
+    fn submit_text_input(&mut self, input: TextInput, text: String) -> Result<()> {
+        let message = match input {
+            TextInput::Describe(preset) => {
+                preset.lock().unwrap().description = (!text.is_empty()).then_some(text);
+                DATA.lock().unwrap().save()?;
+                format!("Description of {} saved", preset.lock().unwrap())
+            }
+            TextInput::Export(_) | TextInput::Import if text.is_empty() => {
+                String::from("A file is needed")
+            }
+            TextInput::Export(preset) => {
+                let presets: Vec<Preset> = match preset {
+                    Some(preset) => vec![preset.lock().unwrap().clone()],
+                    None => DATA
+                        .lock()
+                        .unwrap()
+                        .presets
+                        .iter()
+                        .map(|preset| preset.lock().unwrap().clone())
+                        .collect(),
+                };
+                let path = PathBuf::from(text);
+                // Existing files are refused, overwriting one is only offered from the command line
+                match config::export(&path, &presets, false) {
+                    Ok(()) => format!(
+                        "{} exported to {}",
+                        presets
+                            .iter()
+                            .map(ToString::to_string)
+                            .collect::<Vec<String>>()
+                            .join(", "),
+                        path.display()
+                    ),
+                    Err(err) => format!("Failed to export to {}: {}", path.display(), err),
+                }
+            }
+            TextInput::Import => {
+                let path = PathBuf::from(text);
+                match config::import(&path) {
+                    Ok(imported) => {
+                        let conflicts =
+                            transfer::conflicts(&DATA.lock().unwrap().presets, &imported);
+                        return self.handle_action(if conflicts.is_empty() {
+                            ActionType::ImportPresets(path, OnConflict::Skip)
+                        } else {
+                            ActionType::OpenMenu(Box::new(MenuImport { path, conflicts }))
+                        });
+                    }
+                    Err(err) => format!("Failed to import {}: {}", path.display(), err),
+                }
+            }
+            TextInput::NewPresetName | TextInput::Rename(_) if text.is_empty() => {
+                String::from("A preset needs a name")
+            }
+            TextInput::NewPresetName if name_taken(&text, None) => {
+                format!("A preset named {} already exists", text)
+            }
+            TextInput::Rename(ref preset) if name_taken(&text, Some(preset)) => {
+                format!("A preset named {} already exists", text)
+            }
+            TextInput::NewPresetName => {
+                return self.handle_action(ActionType::SaveCurrentConfigAsPreset(text));
+            }
+            TextInput::Rename(preset) => {
+                let old_name = std::mem::replace(&mut preset.lock().unwrap().name, text.clone());
+                DATA.lock().unwrap().save()?;
+                format!("Preset {} renamed to {}", old_name, text)
+            }
+        };
+        self.handle_action(ActionType::DisplayMessage(message))
+    }
+
+    /// Answers the countdown, restoring the previous settings if they are not kept
+    fn keep_settings(&mut self, keep: bool) -> Result<()> {
+        let Some((applied_message, snapshot, _)) = self.rendered_keep_settings.take() else {
+            return Ok(());
+        };
+
+        let message = if keep {
+            match Monitor::persist_settings(&DATA.lock().unwrap().monitors) {
+                Ok(()) => applied_message,
+                Err(err) => format!("{}, but failed to save them: {}", applied_message, err),
+            }
+        } else {
+            match snapshot.apply(&DATA.lock().unwrap().monitors) {
+                Ok(_) => String::from("Previous display settings restored"),
+                Err(err) => format!("Failed to restore the previous display settings: {}", err),
+            }
+        };
+        self.handle_action(ActionType::DisplayMessage(message))
+    }
+
     fn go_back(&mut self) {
         if self.rendered_change_hotkey.is_some() {
             self.rendered_change_hotkey = None;
@@ -144,7 +323,7 @@ impl App {
         }
 
         self.path.pop();
-        if self.path.len() <= 0 {
+        if self.path.is_empty() {
             self.should_exit = true;
         }
     }
@@ -177,6 +356,46 @@ impl App {
             return;
         }
 
+        if let Some((_, _, deadline)) = &self.rendered_keep_settings {
+            let layout =
+                Layout::vertical([Constraint::Fill(1); 3]).flex(ratatui::layout::Flex::Center);
+            let a = layout.split(area).to_vec();
+            Paragraph::new(confirm::message(
+                deadline.saturating_duration_since(Instant::now()),
+            ))
+            .centered()
+            .block(
+                Block::bordered()
+                    .border_type(BorderType::Rounded)
+                    .border_style(Style::new().fg(HEADER_BACKGROUND_COLOR))
+                    .style(Style::new().bg(NORMAL_ROW_BG)),
+            )
+            .render(a[1], buf);
+            return;
+        }
+
+        if let Some((input, text)) = &self.rendered_text_input {
+            let block = Block::bordered()
+                .title(input.title())
+                .title_alignment(Alignment::Center)
+                .title_style(Style::new().fg(TEXT_FG_COLOR))
+                .border_type(BorderType::Rounded)
+                .border_style(Style::new().fg(HEADER_BACKGROUND_COLOR))
+                .style(Style::new().bg(NORMAL_ROW_BG));
+
+            let inner_area = block.inner(area);
+            block.render(area, buf);
+
+            let layout =
+                Layout::vertical([Constraint::Fill(1); 3]).flex(ratatui::layout::Flex::Center);
+            let a = layout.split(inner_area).to_vec();
+
+            Paragraph::new(format!("{}▏", text))
+                .centered()
+                .render(a[1], buf);
+            return;
+        }
+
         if let Some((preset, input)) = &mut self.rendered_change_hotkey {
             let block = Block::bordered()
                 .title(format!("Editing Shortcut for {}", preset.lock().unwrap()))
@@ -217,7 +436,9 @@ impl App {
     fn render_footer(&self, area: Rect, buf: &mut Buffer) {
         Paragraph::new(if self.rendered_message.is_some() {
             "Press any key to continue ↩"
-        } else if self.rendered_change_hotkey.is_some() {
+        } else if self.rendered_keep_settings.is_some() {
+            "Press Enter to keep, Escape to revert"
+        } else if self.rendered_change_hotkey.is_some() || self.rendered_text_input.is_some() {
             "Press Escape to cancel, Enter to confirm"
         } else {
             "Use ←↓↑→ or hjkl to move"
@@ -229,14 +450,99 @@ impl App {
     fn handle_actions(&mut self, actions: Vec<ActionType>) -> Result<()> {
         actions
             .into_iter()
-            .map(|action| self.handle_action(action))
-            .collect()
+            .try_for_each(|action| self.handle_action(action))
     }
 
     fn handle_action(&mut self, action: ActionType) -> Result<()> {
         match action {
             ActionType::ApplyPreset(preset) => {
-                preset.lock().unwrap().apply(&DATA.lock().unwrap().monitors)
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let snapshot = Preset::from_current_config(&monitors)?;
+                // Refused or rolled back presets leave the settings as they were, the app goes on
+                let result = preset.lock().unwrap().apply(&monitors);
+                let report = match result {
+                    Ok(report) => report,
+                    Err(err) => {
+                        return self.handle_action(ActionType::DisplayMessage(format!(
+                            "Failed to apply preset {}: {}",
+                            preset.lock().unwrap(),
+                            err
+                        )));
+                    }
+                };
+
+                let mut message = match report.outcome {
+                    ApplyOutcome::Applied => {
+                        format!("Preset {} successfully applied", preset.lock().unwrap())
+                    }
+                    ApplyOutcome::AlreadyApplied => {
+                        format!("Preset {} is already applied", preset.lock().unwrap())
+                    }
+                };
+                if !report.skipped.is_empty() {
+                    message.push_str(&format!(
+                        ", missing monitors skipped: {}",
+                        report.skipped.join(", ")
+                    ));
+                }
+                message.push_str(&diagnostics_message(&report.diagnostics));
+
+                match report.outcome {
+                    ApplyOutcome::Applied => {
+                        self.rendered_keep_settings =
+                            Some((message, snapshot, Instant::now() + confirm::TIMEOUT));
+                        Ok(())
+                    }
+                    ApplyOutcome::AlreadyApplied => {
+                        self.handle_action(ActionType::DisplayMessage(message))
+                    }
+                }
+            }
+            ActionType::CycleMissingMonitorPolicy(preset) => {
+                let policy = {
+                    let mut preset = preset.lock().unwrap();
+                    preset.on_missing_monitor = preset.on_missing_monitor.next();
+                    preset.on_missing_monitor
+                };
+                DATA.lock().unwrap().save()?;
+                self.handle_action(ActionType::DisplayMessage(format!(
+                    "Missing monitors are now handled as: {}",
+                    policy
+                )))
+            }
+            ActionType::CheckPreset(preset) => {
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let diagnostics = preset.lock().unwrap().check(&monitors)?;
+                let message = if diagnostics.is_empty() {
+                    format!(
+                        "No problem found in the layout of {}",
+                        preset.lock().unwrap()
+                    )
+                } else {
+                    format!(
+                        "Layout of {}:{}",
+                        preset.lock().unwrap(),
+                        diagnostics_message(&diagnostics)
+                    )
+                };
+                self.handle_action(ActionType::DisplayMessage(message))
+            }
+            ActionType::NormalizePreset(preset) => {
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let moved = preset.lock().unwrap().normalize(&monitors)?;
+                let message = if moved {
+                    DATA.lock().unwrap().save()?;
+                    format!("Layout of {} normalised", preset.lock().unwrap())
+                } else {
+                    format!("Layout of {} is already normalised", preset.lock().unwrap())
+                };
+                self.handle_action(ActionType::DisplayMessage(message))
+            }
+            ActionType::DryRunPreset(preset) => {
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let dry_run = preset.lock().unwrap().dry_run(&monitors)?;
+                self.rendered_message = Some(dry_run.to_string());
+                Ok(())
             }
             ActionType::OpenMenu(menu) => {
                 self.path.push(RenderedMenu::from(menu));
@@ -246,14 +552,23 @@ impl App {
                 self.go_back();
                 Ok(())
             }
-            ActionType::SaveCurrentConfigAsPreset => {
-                let preset = { Preset::from_current_config(&DATA.lock().unwrap().monitors)? };
-                DATA.lock()
-                    .unwrap()
-                    .presets
-                    .push(Arc::new(Mutex::new(preset)));
+            ActionType::SaveCurrentConfigAsPreset(name) => {
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let mut preset = Preset::from_current_config(&monitors)?;
+                preset.name = name;
+                let mut message = format!("Current preset successfully saved as {}", preset);
+                message.push_str(&diagnostics_message(&preset.check(&monitors)?));
+                // Captured layouts often are a few pixels off
+                if preset.clone().normalize(&monitors)? {
+                    message.push_str("\nIts layout is slightly misaligned, it can be normalised");
+                }
+
+                let preset = Arc::new(Mutex::new(preset));
+                DATA.lock().unwrap().presets.push(preset.clone());
                 DATA.lock().unwrap().save()?;
-                Ok(())
+
+                self.handle_action(ActionType::OpenMenu(Box::new(MenuManagePreset { preset })))?;
+                self.handle_action(ActionType::DisplayMessage(message))
             }
             ActionType::DeletePreset(preset) => {
                 let (index, _) = DATA
@@ -268,18 +583,47 @@ impl App {
                 DATA.lock().unwrap().save()?;
                 Ok(())
             }
+            ActionType::ImportPresets(path, on_conflict) => {
+                let message = match config::import(&path) {
+                    Ok(imported) => {
+                        let mut data = DATA.lock().unwrap();
+                        let report = transfer::import(&mut data.presets, imported, on_conflict);
+                        data.save()?;
+                        report.to_string()
+                    }
+                    Err(err) => format!("Failed to import {}: {}", path.display(), err),
+                };
+                self.handle_action(ActionType::DisplayMessage(message))
+            }
             ActionType::DisplayMessage(message) => {
                 self.rendered_message = Some(message);
                 Ok(())
             }
+            ActionType::TextInput(input) => {
+                let text = input.initial_text();
+                self.rendered_text_input = Some((input, text));
+                Ok(())
+            }
             ActionType::ChangeHotkeyInput(preset) => {
                 self.rendered_change_hotkey = Some((preset, None));
-                self.console_mode = set_console_to_input_mode(self.console_handle)?;
+                #[cfg(windows)]
+                {
+                    self.console_mode = set_console_to_input_mode(self.console_handle)?;
+                }
                 Ok(())
             }
             ActionType::StartHeadless => {
+                if let Err(err) = confirm::check() {
+                    return self.handle_action(ActionType::DisplayMessage(format!(
+                        "Hotkeys can't be started: {}",
+                        err
+                    )));
+                }
+
                 Command::new(std::env::current_exe()?)
                     .arg(Self::HEADLESS_ARG)
+                    .arg(Self::CONFIG_ARG)
+                    .arg(&DATA.lock().unwrap().config_path)
                     .stdin(Stdio::piped())
                     .stdout(Stdio::piped())
                     .spawn()?;
@@ -289,32 +633,52 @@ impl App {
                 Ok(())
             }
             ActionType::ToggleStartup => {
-                let startup_dir = dirs::template_dir()
-                    .unwrap()
-                    .parent()
-                    .unwrap()
-                    .join("Start Menu/Programs/Startup");
-
-                let startup_path = startup_dir.join("WiMLaM.vbs");
+                let exe = std::env::current_exe()?;
+                let config_path = DATA.lock().unwrap().config_path.clone();
+
+                #[cfg(windows)]
+                let (startup_path, content) = (
+                    dirs::template_dir()
+                        .unwrap()
+                        .parent()
+                        .unwrap()
+                        .join("Start Menu/Programs/Startup/WiMLaM.vbs"),
+                    format!(
+                        "CreateObject(\"Wscript.Shell\").Run \"\"\"{}\"\" {} {} \"\"{}\"\"\", 0, True",
+                        exe.to_string_lossy(),
+                        Self::HEADLESS_ARG,
+                        Self::CONFIG_ARG,
+                        config_path.to_string_lossy()
+                    ),
+                );
+
+                // XDG autostart entry, started by the desktop environment on login
+                #[cfg(not(windows))]
+                let (startup_path, content) = (
+                    dirs::config_dir()
+                        .ok_or(ThisError::NoConfigDir)?
+                        .join("autostart/WiMLaM.desktop"),
+                    format!(
+                        "[Desktop Entry]\nType=Application\nName=WiMLaM\nExec=\"{}\" {} {} \"{}\"\n",
+                        exe.to_string_lossy(),
+                        Self::HEADLESS_ARG,
+                        Self::CONFIG_ARG,
+                        config_path.to_string_lossy()
+                    ),
+                );
 
                 if !startup_path.exists() {
-                    fs::write(
-                        startup_path,
-                        format!(
-                            "CreateObject(\"Wscript.Shell\").Run \"{} {}\", 0, True",
-                            std::env::current_exe()?.to_string_lossy(),
-                            Self::HEADLESS_ARG
-                        ),
-                    )?;
-
-                    self.handle_action(ActionType::DisplayMessage(format!(
-                        "Successfully added app to opening on windows startup ",
+                    fs::create_dir_all(startup_path.parent().unwrap())?;
+                    fs::write(startup_path, content)?;
+
+                    self.handle_action(ActionType::DisplayMessage(String::from(
+                        "Successfully added app to opening on startup ",
                     )))?;
                 } else {
                     fs::remove_file(startup_path)?;
 
-                    self.handle_action(ActionType::DisplayMessage(format!(
-                        "Successfully removed app from opening on windows startup ",
+                    self.handle_action(ActionType::DisplayMessage(String::from(
+                        "Successfully removed app from opening on startup ",
                     )))?;
                 }
 
@@ -338,3 +702,19 @@ impl Widget for &mut App {
         self.render_content(content_area, buf)
     }
 }
+
+/// One line per problem of a layout, starting with a line break
+fn diagnostics_message(diagnostics: &[Diagnostic]) -> String {
+    diagnostics
+        .iter()
+        .map(|diagnostic| format!("\n⚠ {}", diagnostic))
+        .collect()
+}
+
+/// Whether another preset than the one renamed, if any, already has the name, presets being told apart by their name
+fn name_taken(name: &str, renamed: Option<&Arc<Mutex<Preset>>>) -> bool {
+    DATA.lock().unwrap().presets.iter().any(|preset| {
+        renamed.is_none_or(|renamed| !Arc::ptr_eq(preset, renamed))
+            && preset.lock().unwrap().name == name
+    })
+}
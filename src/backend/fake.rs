@@ -0,0 +1,294 @@
+use crate::{
+    backend::{DisplayBackend, MonitorDevice, Origin},
+    display_settings::{DisplayMode, DisplayOrientation, DisplaySettings, Pos, Resolution},
+    edid::Edid,
+    error::ThisError,
+    monitor::{Monitor, MonitorName},
+};
+use color_eyre::eyre::Result;
+use serde::Deserialize;
+use std::{collections::BTreeMap, fs, path::PathBuf, sync::Mutex};
+
+/// In-memory backend simulating a set of monitors, every apply call is recorded
+///
+/// The monitors can be configured with a JSON file (a map of monitor names to display settings)
+/// pointed to by the `WIMLAM_FAKE_MONITORS` env var, each monitor can point to an EDID file with an `edid` field.
+/// Monitors with an EDID are named after it like on real hardware, the key of the file being their legacy name
+#[derive(Debug)]
+pub struct FakeBackend {
+    monitors: Mutex<BTreeMap<MonitorName, DisplaySettings>>,
+    edids: BTreeMap<MonitorName, Edid>,
+    applied: Mutex<Vec<(MonitorName, DisplaySettings)>>,
+    origin: Origin,
+    /// Makes the next apply call fail once it changed the monitors, like a driver giving up halfway
+    #[cfg(test)]
+    fail_next_apply: Mutex<bool>,
+}
+
+/// Monitor of the `WIMLAM_FAKE_MONITORS` file
+#[derive(Debug, Deserialize)]
+struct FakeMonitor {
+    #[serde(flatten)]
+    settings: DisplaySettings,
+    /// EDID file, relative to the monitors file
+    #[serde(default)]
+    edid: Option<PathBuf>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self::new([
+            (
+                String::from("\\\\.\\DISPLAY1\\Monitor0"),
+                DisplaySettings {
+                    position: Pos { x: 0, y: 0 },
+                    placement: None,
+                    orientation: DisplayOrientation::Landscape,
+                    resolution: Some(Resolution {
+                        width: 1920,
+                        height: 1080,
+                    }),
+                    refresh_rate: Some(60),
+                    scale: None,
+                    flipped: false,
+                    primary: true,
+                    enabled: true,
+                    mirror: None,
+                },
+            ),
+            (
+                String::from("\\\\.\\DISPLAY2\\Monitor0"),
+                DisplaySettings {
+                    position: Pos { x: 1920, y: 0 },
+                    placement: None,
+                    orientation: DisplayOrientation::Landscape,
+                    resolution: Some(Resolution {
+                        width: 2560,
+                        height: 1440,
+                    }),
+                    refresh_rate: Some(144),
+                    scale: None,
+                    flipped: false,
+                    primary: false,
+                    enabled: true,
+                    mirror: None,
+                },
+            ),
+        ])
+        .with_edid(
+            "\\\\.\\DISPLAY1\\Monitor0",
+            include_bytes!("../../fixtures/edid/dell_u2720q.bin"),
+        )
+        .and_then(|backend| {
+            backend.with_edid(
+                "\\\\.\\DISPLAY2\\Monitor0",
+                include_bytes!("../../fixtures/edid/lg_27gl850.bin"),
+            )
+        })
+        .unwrap()
+    }
+}
+
+impl FakeBackend {
+    pub const MONITORS_ENV: &'static str = "WIMLAM_FAKE_MONITORS";
+    /// Modes supported by every simulated monitor, on top of its current one
+    const MODES: [(u32, u32, u32); 6] = [
+        (1280, 720, 60),
+        (1920, 1080, 60),
+        (1920, 1080, 144),
+        (2560, 1440, 60),
+        (2560, 1440, 144),
+        (3840, 2160, 60),
+    ];
+
+    pub fn new(monitors: impl IntoIterator<Item = (MonitorName, DisplaySettings)>) -> Self {
+        Self {
+            monitors: Mutex::new(monitors.into_iter().collect()),
+            edids: BTreeMap::new(),
+            applied: Mutex::new(Vec::new()),
+            origin: Origin::Primary,
+            #[cfg(test)]
+            fail_next_apply: Mutex::new(false),
+        }
+    }
+
+    /// Gives the monitor the EDID read from the blob
+    pub fn with_edid(mut self, monitor: impl Into<MonitorName>, edid: &[u8]) -> Result<Self> {
+        self.edids.insert(monitor.into(), Edid::parse(edid)?);
+        Ok(self)
+    }
+
+    /// Puts the layouts it applies where a backend with that origin does
+    #[cfg(test)]
+    pub fn with_origin(mut self, origin: Origin) -> Self {
+        self.origin = origin;
+        self
+    }
+
+    pub fn from_env() -> Result<Self> {
+        let Ok(path) = std::env::var(Self::MONITORS_ENV) else {
+            return Ok(Self::default());
+        };
+        let path = PathBuf::from(path);
+
+        let monitors: BTreeMap<MonitorName, FakeMonitor> =
+            serde_json::from_str(&fs::read_to_string(&path)?)?;
+        let mut backend = Self::new(
+            monitors
+                .iter()
+                .map(|(name, monitor)| (name.clone(), monitor.settings.clone())),
+        );
+        for (name, monitor) in monitors {
+            if let Some(edid) = monitor.edid {
+                let edid = path.parent().unwrap_or(&path).join(edid);
+                backend = backend.with_edid(name, &fs::read(edid)?)?;
+            }
+        }
+        Ok(backend)
+    }
+
+    /// Applies the settings to the simulated monitors
+    fn simulate(
+        &self,
+        monitors: &mut BTreeMap<MonitorName, DisplaySettings>,
+        settings: &[(&Monitor, &DisplaySettings)],
+    ) -> Result<()> {
+        for (monitor, display_settings) in settings {
+            if display_settings.primary {
+                monitors
+                    .values_mut()
+                    .for_each(|settings| settings.primary = false);
+            }
+
+            let current = monitors
+                .get_mut(&monitor.id)
+                .ok_or(ThisError::MonitorNotFound)?;
+            if display_settings.enabled {
+                *current = DisplaySettings {
+                    resolution: display_settings.resolution.or(current.resolution),
+                    refresh_rate: display_settings.refresh_rate.or(current.refresh_rate),
+                    scale: display_settings.scale.or(current.scale),
+                    ..(*display_settings).clone()
+                };
+            } else {
+                current.enabled = false;
+                current.primary = false;
+            }
+        }
+
+        if self.origin == Origin::TopLeft {
+            let enabled = || monitors.values().filter(|settings| settings.enabled);
+            let min_x = enabled().map(|settings| settings.position.x).min();
+            let min_y = enabled().map(|settings| settings.position.y).min();
+            if let (Some(min_x), Some(min_y)) = (min_x, min_y) {
+                for settings in monitors.values_mut() {
+                    settings.position.x -= min_x;
+                    settings.position.y -= min_y;
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Every settings change applied so far, in order
+    #[cfg(test)]
+    pub fn applied(&self) -> Vec<(MonitorName, DisplaySettings)> {
+        self.applied.lock().unwrap().clone()
+    }
+
+    #[cfg(test)]
+    pub fn fail_next_apply(&self) {
+        *self.fail_next_apply.lock().unwrap() = true;
+    }
+}
+
+impl DisplayBackend for FakeBackend {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>> {
+        Ok(self
+            .monitors
+            .lock()
+            .unwrap()
+            .keys()
+            .map(|key| {
+                let edid = self.edids.get(key).cloned();
+                MonitorDevice {
+                    name: edid
+                        .as_ref()
+                        .and_then(Edid::stable_name)
+                        .unwrap_or(key.clone()),
+                    id: key.clone(),
+                    legacy_name: edid.is_some().then(|| key.clone()),
+                    edid,
+                }
+            })
+            .collect())
+    }
+
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings> {
+        Ok(self
+            .monitors
+            .lock()
+            .unwrap()
+            .get(&monitor.id)
+            .ok_or(ThisError::EnumDisplaySettings)?
+            .clone())
+    }
+
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>> {
+        let current = self.get_display_settings(monitor)?;
+
+        let mut modes: Vec<DisplayMode> = Self::MODES
+            .iter()
+            .map(|(width, height, refresh_rate)| DisplayMode {
+                resolution: Resolution {
+                    width: *width,
+                    height: *height,
+                },
+                refresh_rate: *refresh_rate,
+                bits_per_pixel: Some(32),
+            })
+            .collect();
+        if let (Some(resolution), Some(refresh_rate)) = (current.resolution, current.refresh_rate) {
+            modes.push(DisplayMode {
+                resolution,
+                refresh_rate,
+                bits_per_pixel: Some(32),
+            });
+        }
+
+        Ok(modes)
+    }
+
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        self.simulate(&mut self.monitors.lock().unwrap(), settings)?;
+
+        self.applied
+            .lock()
+            .unwrap()
+            .extend(settings.iter().map(|(monitor, display_settings)| {
+                (monitor.name.clone(), (*display_settings).clone())
+            }));
+
+        #[cfg(test)]
+        if std::mem::take(&mut *self.fail_next_apply.lock().unwrap())
+            && let Some((monitor, _)) = settings.last()
+        {
+            Err(ThisError::ChangeMonitorSettings {
+                monitor: monitor.name.clone(),
+                reason: String::from("simulated failure"),
+            })?;
+        }
+        Ok(())
+    }
+
+    /// Simulates the settings on a copy of the monitors, nothing is recorded
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        let mut monitors = self.monitors.lock().unwrap().clone();
+        self.simulate(&mut monitors, settings)
+    }
+
+    fn origin(&self) -> Origin {
+        self.origin
+    }
+}
@@ -0,0 +1,125 @@
+pub mod fake;
+#[cfg(not(windows))]
+pub mod mutter;
+#[cfg(windows)]
+pub mod win32;
+#[cfg(not(windows))]
+pub mod wlr;
+#[cfg(not(windows))]
+pub mod x11;
+
+use crate::{
+    display_settings::{DisplayMode, DisplaySettings},
+    edid::Edid,
+    error::ThisError,
+    monitor::{Monitor, MonitorName},
+};
+use color_eyre::eyre::Result;
+use std::{fmt::Debug, sync::Arc};
+
+/// A monitor as reported by a backend, before being bound to it
+#[derive(Debug, Clone)]
+pub struct MonitorDevice {
+    /// Identifies the monitor whatever port it is plugged in, see [`MonitorDevice::stable_name`]
+    pub name: MonitorName,
+    /// Backend specific handle (adapter name, output id...) passed back through [`Monitor::id`]
+    pub id: String,
+    /// Name the backend used to give the monitor, presets keyed on it are migrated to [`MonitorDevice::name`]
+    pub legacy_name: Option<MonitorName>,
+    pub edid: Option<Edid>,
+}
+
+impl MonitorDevice {
+    /// Names a monitor after its manufacturer, product and serial, `None` when the monitor doesn't report them
+    pub fn stable_name(manufacturer: &str, product: &str, serial: &str) -> Option<MonitorName> {
+        if manufacturer.trim().is_empty() && product.trim().is_empty() {
+            return None;
+        }
+
+        let parts: Vec<&str> = [manufacturer, product, serial]
+            .into_iter()
+            .map(str::trim)
+            .filter(|part| !part.is_empty())
+            .collect();
+        Some(parts.join(" "))
+    }
+
+    /// Monitor plugged in a connector of a Wayland compositor, named like on the other backends
+    ///
+    /// Compositors only report the make, model and serial as text, the EDID is read from sysfs when it can be
+    #[cfg(not(windows))]
+    pub fn from_connector(connector: &str, make: &str, model: &str, serial: &str) -> Self {
+        let edid = Edid::from_sysfs(connector);
+        Self {
+            name: edid
+                .as_ref()
+                .and_then(Edid::stable_name)
+                .or_else(|| Edid::stable_name_from_text(make, model, serial))
+                .unwrap_or(connector.to_string()),
+            id: connector.to_string(),
+            legacy_name: Some(connector.to_string()),
+            edid,
+        }
+    }
+}
+
+/// Where a backend puts the layouts it applies
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+    /// Positions are applied as given, presets putting their primary monitor at 0,0
+    Primary,
+    /// Layouts are moved so their top left corner sits at 0,0
+    TopLeft,
+}
+
+/// Platform API used to list monitors and read or change their settings
+pub trait DisplayBackend: Debug + Send + Sync {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>>;
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings>;
+    /// Every mode the monitor supports
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>>;
+    /// Applies the settings of every listed monitor, monitors not listed are left untouched
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()>;
+    /// Checks the settings of every listed monitor could be applied, without changing anything
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()>;
+    /// Saves the settings applied last so they survive a restart, once the user kept them
+    fn persist(&self) -> Result<()> {
+        Ok(())
+    }
+    /// Where the backend puts the layouts it applies, current settings are read in the same coordinates
+    fn origin(&self) -> Origin {
+        Origin::Primary
+    }
+}
+
+pub const BACKEND_ENV: &str = "WIMLAM_BACKEND";
+
+/// Picks the backend from the `WIMLAM_BACKEND` env var, or the platform's default one
+pub fn from_env() -> Result<Arc<dyn DisplayBackend>> {
+    match std::env::var(BACKEND_ENV).ok().as_deref() {
+        Some("fake") => Ok(Arc::new(fake::FakeBackend::from_env()?)),
+        #[cfg(windows)]
+        Some("windows") | None => Ok(Arc::new(win32::WindowsBackend)),
+        #[cfg(not(windows))]
+        Some("x11") => Ok(Arc::new(x11::X11Backend::connect()?)),
+        #[cfg(not(windows))]
+        Some("wlr") => Ok(Arc::new(wlr::WlrBackend::connect()?)),
+        #[cfg(not(windows))]
+        Some("mutter") => Ok(Arc::new(mutter::MutterBackend::connect()?)),
+        #[cfg(not(windows))]
+        None if std::env::var("XDG_CURRENT_DESKTOP")
+            .is_ok_and(|desktop| desktop.contains("GNOME")) =>
+        {
+            Ok(Arc::new(mutter::MutterBackend::connect()?))
+        }
+        #[cfg(not(windows))]
+        None if std::env::var_os("WAYLAND_DISPLAY").is_some() => {
+            Ok(Arc::new(wlr::WlrBackend::connect()?))
+        }
+        #[cfg(not(windows))]
+        None if std::env::var_os("DISPLAY").is_some() => Ok(Arc::new(x11::X11Backend::connect()?)),
+        #[cfg(not(windows))]
+        None => Ok(Arc::new(fake::FakeBackend::from_env()?)),
+        Some(other) => Err(ThisError::UnknownBackend(other.to_string()))?,
+    }
+}
@@ -0,0 +1,740 @@
+use crate::{
+    backend::{DisplayBackend, MonitorDevice, Origin},
+    display_settings::{DisplayMode, DisplayOrientation, DisplaySettings, Pos, Resolution},
+    error::ThisError,
+    monitor::Monitor,
+};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use zbus::{
+    blocking::Connection,
+    zvariant::{OwnedValue, Type, Value},
+};
+
+type Properties = HashMap<String, OwnedValue>;
+
+/// Connector, vendor, product and serial of a physical monitor
+#[derive(Debug, Clone, Deserialize, Type)]
+struct MonitorSpec {
+    connector: String,
+    vendor: String,
+    product: String,
+    serial: String,
+}
+
+#[derive(Debug, Deserialize, Type)]
+struct MonitorMode {
+    id: String,
+    width: i32,
+    height: i32,
+    refresh_rate: f64,
+    preferred_scale: f64,
+    _supported_scales: Vec<f64>,
+    properties: Properties,
+}
+
+impl MonitorMode {
+    fn resolution(&self) -> Resolution {
+        Resolution {
+            width: self.width as u32,
+            height: self.height as u32,
+        }
+    }
+
+    fn rounded_refresh_rate(&self) -> u32 {
+        self.refresh_rate.round() as u32
+    }
+}
+
+#[derive(Debug, Deserialize, Type)]
+struct PhysicalMonitor {
+    spec: MonitorSpec,
+    modes: Vec<MonitorMode>,
+    _properties: Properties,
+}
+
+#[derive(Debug, Deserialize, Type)]
+struct LogicalMonitor {
+    x: i32,
+    y: i32,
+    scale: f64,
+    transform: u32,
+    primary: bool,
+    monitors: Vec<MonitorSpec>,
+    _properties: Properties,
+}
+
+#[derive(Debug, Deserialize, Type)]
+struct CurrentState {
+    serial: u32,
+    monitors: Vec<PhysicalMonitor>,
+    logical_monitors: Vec<LogicalMonitor>,
+    properties: Properties,
+}
+
+impl CurrentState {
+    /// Whether logical monitors are laid out in scaled pixels (the default on Wayland) rather than in the pixels of their mode
+    fn is_layout_logical(&self) -> bool {
+        self.properties
+            .get("layout-mode")
+            .and_then(|v| u32::try_from(v).ok())
+            == Some(1)
+    }
+}
+
+/// Logical monitor as expected by `ApplyMonitorsConfig`
+#[derive(Debug, Serialize, Type)]
+struct LogicalMonitorConfig<'a> {
+    x: i32,
+    y: i32,
+    scale: f64,
+    transform: u32,
+    primary: bool,
+    /// Connector, mode id and properties of every monitor of the logical monitor
+    monitors: Vec<(&'a str, &'a str, HashMap<&'a str, Value<'a>>)>,
+}
+
+/// How `ApplyMonitorsConfig` should apply a configuration
+#[derive(Debug, Clone, Copy)]
+#[repr(u32)]
+pub enum ApplyMethod {
+    /// Only checked by mutter, nothing changes
+    Verify = 0,
+    /// Reverted on the next session or hotplug
+    Temporary = 1,
+    /// Saved to `monitors.xml`, gnome-shell asks the user to keep it on top of WiMLaM's own countdown
+    Persistent = 2,
+}
+
+/// Drives the monitors of a GNOME session through `org.gnome.Mutter.DisplayConfig`,
+/// a monitor's id is its connector name and its name is read from its EDID, see [`MonitorDevice::from_connector`]
+///
+/// Settings are applied temporarily and saved once the user keeps them, see [`DisplayBackend::persist`],
+/// unless the `WIMLAM_MUTTER_METHOD` env var is `persistent`. In the logical layout mode of Wayland sessions,
+/// positions are in scaled pixels and the monitors' scale is captured, see [`Resolution::scaled`]
+#[derive(Debug)]
+pub struct MutterBackend {
+    conn: Connection,
+    method: ApplyMethod,
+}
+
+/// Transforms are counted counterclockwise like `wl_output`, the flipped ones coming after the 4 others
+fn orientation_from_transform(transform: u32) -> DisplayOrientation {
+    DisplayOrientation::try_from(transform % 4).unwrap()
+}
+
+const FLIPPED_TRANSFORMS: u32 = 4;
+
+fn transform(orientation: DisplayOrientation, flipped: bool) -> u32 {
+    orientation as u32 + if flipped { FLIPPED_TRANSFORMS } else { 0 }
+}
+
+impl MutterBackend {
+    pub const METHOD_ENV: &'static str = "WIMLAM_MUTTER_METHOD";
+    const DESTINATION: &'static str = "org.gnome.Mutter.DisplayConfig";
+    const PATH: &'static str = "/org/gnome/Mutter/DisplayConfig";
+
+    /// Connects to the session bus
+    pub fn connect() -> Result<Self> {
+        let method = match std::env::var(Self::METHOD_ENV).ok().as_deref() {
+            Some("persistent") => ApplyMethod::Persistent,
+            _ => ApplyMethod::Temporary,
+        };
+
+        Ok(Self {
+            conn: Connection::session()?,
+            method,
+        })
+    }
+
+    fn get_current_state(&self) -> Result<CurrentState> {
+        let reply = self.conn.call_method(
+            Some(Self::DESTINATION),
+            Self::PATH,
+            Some(Self::DESTINATION),
+            "GetCurrentState",
+            &(),
+        )?;
+        Ok(reply.body().deserialize()?)
+    }
+
+    fn find_modes<'a>(
+        state: &'a CurrentState,
+        connector: &str,
+    ) -> impl Iterator<Item = &'a MonitorMode> {
+        state
+            .monitors
+            .iter()
+            .filter(move |m| m.spec.connector == connector)
+            .flat_map(|m| &m.modes)
+    }
+
+    fn current_mode<'a>(state: &'a CurrentState, connector: &str) -> Option<&'a MonitorMode> {
+        Self::find_modes(state, connector).find(|mode| {
+            mode.properties
+                .get("is-current")
+                .and_then(|v| bool::try_from(v).ok())
+                .unwrap_or(false)
+        })
+    }
+
+    fn preferred_mode<'a>(state: &'a CurrentState, connector: &str) -> Option<&'a MonitorMode> {
+        Self::find_modes(state, connector).find(|mode| {
+            mode.properties
+                .get("is-preferred")
+                .and_then(|v| bool::try_from(v).ok())
+                .unwrap_or(false)
+        })
+    }
+
+    fn find_logical_monitor<'a>(
+        state: &'a CurrentState,
+        monitor: &Monitor,
+    ) -> Option<&'a LogicalMonitor> {
+        state.logical_monitors.iter().find(|logical| {
+            logical
+                .monitors
+                .iter()
+                .any(|spec| spec.connector == monitor.id)
+        })
+    }
+
+    fn apply_monitors_config(
+        &self,
+        settings: &[(&Monitor, &DisplaySettings)],
+        method: ApplyMethod,
+    ) -> Result<()> {
+        let state = self.get_current_state()?;
+
+        // Monitors not listed keep their current state, monitors left out of the config would be turned off
+        let mut logical_monitors = state
+            .logical_monitors
+            .iter()
+            .map(|logical| {
+                let monitors = logical
+                    .monitors
+                    .iter()
+                    .map(|spec| {
+                        let mode = Self::current_mode(&state, &spec.connector)
+                            .ok_or(ThisError::EnumDisplaySettings)?;
+                        Ok((spec.connector.as_str(), mode.id.as_str(), HashMap::new()))
+                    })
+                    .collect::<Result<_>>()?;
+
+                Ok(LogicalMonitorConfig {
+                    x: logical.x,
+                    y: logical.y,
+                    scale: logical.scale,
+                    transform: logical.transform,
+                    primary: logical.primary,
+                    monitors,
+                })
+            })
+            .collect::<Result<Vec<_>>>()?;
+
+        for (monitor, display_settings) in settings {
+            if !state
+                .monitors
+                .iter()
+                .any(|physical| physical.spec.connector == monitor.id)
+            {
+                Err(ThisError::MonitorNotFound)?;
+            }
+
+            // Listed monitors are taken out of their logical monitor and get one of their own
+            let mut scale = None;
+            let mut primary = false;
+            if let Some(index) = logical_monitors.iter().position(|logical| {
+                logical
+                    .monitors
+                    .iter()
+                    .any(|(connector, ..)| *connector == monitor.id)
+            }) {
+                let logical = &mut logical_monitors[index];
+                logical
+                    .monitors
+                    .retain(|(connector, ..)| *connector != monitor.id);
+                scale = Some(logical.scale);
+                if logical.monitors.is_empty() {
+                    primary = logical.primary;
+                    logical_monitors.remove(index);
+                }
+            }
+
+            if !display_settings.enabled {
+                continue;
+            }
+
+            // Turned off monitors have no current mode, their preferred one is used instead
+            let current = Self::current_mode(&state, &monitor.id)
+                .or_else(|| Self::preferred_mode(&state, &monitor.id));
+            let resolution = display_settings
+                .resolution
+                .or(current.map(MonitorMode::resolution));
+            let refresh_rate = display_settings
+                .refresh_rate
+                .or(current.map(MonitorMode::rounded_refresh_rate));
+
+            let mode = Self::find_modes(&state, &monitor.id)
+                .filter(|mode| resolution.is_none_or(|r| mode.resolution() == r))
+                .filter(|mode| refresh_rate.is_none_or(|r| mode.rounded_refresh_rate() == r))
+                .max_by(|a, b| a.refresh_rate.total_cmp(&b.refresh_rate))
+                .ok_or_else(|| ThisError::ChangeMonitorSettings {
+                    monitor: monitor.name.clone(),
+                    reason: String::from("no such mode"),
+                })?;
+
+            if display_settings.primary {
+                logical_monitors
+                    .iter_mut()
+                    .for_each(|logical| logical.primary = false);
+                primary = true;
+            }
+
+            logical_monitors.push(LogicalMonitorConfig {
+                x: display_settings.position.x,
+                y: display_settings.position.y,
+                scale: display_settings
+                    .scale
+                    .or(scale)
+                    .unwrap_or(mode.preferred_scale),
+                transform: transform(display_settings.orientation, display_settings.flipped),
+                primary,
+                monitors: vec![(monitor.id.as_str(), mode.id.as_str(), HashMap::new())],
+            });
+        }
+
+        // Mutter mirrors the monitors of a same logical monitor, the ones sharing their position are grouped
+        let mut merged: Vec<LogicalMonitorConfig> = Vec::new();
+        for logical in logical_monitors {
+            match merged
+                .iter_mut()
+                .find(|other| other.x == logical.x && other.y == logical.y)
+            {
+                Some(other) => {
+                    other.primary |= logical.primary;
+                    other.monitors.extend(logical.monitors);
+                }
+                None => merged.push(logical),
+            }
+        }
+        let mut logical_monitors = merged;
+
+        // Exactly one logical monitor must be primary
+        if !logical_monitors.iter().any(|logical| logical.primary)
+            && let Some(logical) = logical_monitors.first_mut()
+        {
+            logical.primary = true;
+        }
+
+        // Mutter expects the layout's top left corner to sit at 0,0
+        let min_x = logical_monitors
+            .iter()
+            .map(|l| l.x)
+            .min()
+            .unwrap_or_default();
+        let min_y = logical_monitors
+            .iter()
+            .map(|l| l.y)
+            .min()
+            .unwrap_or_default();
+        for logical in &mut logical_monitors {
+            logical.x -= min_x;
+            logical.y -= min_y;
+        }
+
+        self.conn
+            .call_method(
+                Some(Self::DESTINATION),
+                Self::PATH,
+                Some(Self::DESTINATION),
+                "ApplyMonitorsConfig",
+                &(
+                    state.serial,
+                    method as u32,
+                    logical_monitors,
+                    HashMap::<&str, Value>::new(),
+                ),
+            )
+            .map_err(|err| ThisError::ChangeSettings(err.to_string()))?;
+
+        Ok(())
+    }
+}
+
+impl DisplayBackend for MutterBackend {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>> {
+        let state = self.get_current_state()?;
+        Ok(state
+            .monitors
+            .iter()
+            .map(|monitor| {
+                let spec = &monitor.spec;
+                MonitorDevice::from_connector(
+                    &spec.connector,
+                    &spec.vendor,
+                    &spec.product,
+                    &spec.serial,
+                )
+            })
+            .collect())
+    }
+
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings> {
+        let state = self.get_current_state()?;
+        let Some(logical) = Self::find_logical_monitor(&state, monitor) else {
+            return Ok(DisplaySettings::disabled());
+        };
+
+        let mode = Self::current_mode(&state, &monitor.id);
+
+        Ok(DisplaySettings {
+            position: Pos {
+                x: logical.x,
+                y: logical.y,
+            },
+            placement: None,
+            orientation: orientation_from_transform(logical.transform),
+            resolution: mode.map(MonitorMode::resolution),
+            refresh_rate: mode.map(MonitorMode::rounded_refresh_rate),
+            // Monitors laid out in the pixels of their mode cover their whole resolution whatever their scale
+            scale: state.is_layout_logical().then_some(logical.scale),
+            flipped: logical.transform >= FLIPPED_TRANSFORMS,
+            primary: logical.primary,
+            enabled: true,
+            mirror: None,
+        })
+    }
+
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>> {
+        let state = self.get_current_state()?;
+        Ok(Self::find_modes(&state, &monitor.id)
+            .map(|mode| DisplayMode {
+                resolution: mode.resolution(),
+                refresh_rate: mode.rounded_refresh_rate(),
+                bits_per_pixel: None,
+            })
+            .collect())
+    }
+
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        self.apply_monitors_config(settings, self.method)
+    }
+
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        self.apply_monitors_config(settings, ApplyMethod::Verify)
+    }
+
+    /// Applies the current configuration again, persistently, gnome-shell then asks the user to keep it once more
+    fn persist(&self) -> Result<()> {
+        match self.method {
+            ApplyMethod::Temporary => self.apply_monitors_config(&[], ApplyMethod::Persistent),
+            ApplyMethod::Verify | ApplyMethod::Persistent => Ok(()),
+        }
+    }
+
+    fn origin(&self) -> Origin {
+        Origin::TopLeft
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::Preset;
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::{Arc, Mutex},
+    };
+    use zbus::{blocking::connection, interface};
+
+    /// Private session bus, stopped once dropped
+    struct Bus {
+        process: Child,
+        address: String,
+    }
+
+    impl Bus {
+        fn start() -> Self {
+            let mut process = Command::new("dbus-daemon")
+                .args(["--session", "--print-address", "--nofork"])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("dbus-daemon must be installed to run this test");
+            let mut address = String::new();
+            BufReader::new(process.stdout.take().unwrap())
+                .read_line(&mut address)
+                .unwrap();
+            Self {
+                process,
+                address: address.trim().to_string(),
+            }
+        }
+
+        fn connect(&self) -> connection::Builder<'_> {
+            connection::Builder::address(self.address.as_str()).unwrap()
+        }
+    }
+
+    impl Drop for Bus {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+
+    /// Position, scale, transform, primary flag and connectors of a logical monitor
+    type Logical = (i32, i32, f64, u32, bool, Vec<String>);
+
+    struct MockMonitor {
+        spec: (String, String, String, String),
+        /// Id, width, height and preferred scale of every mode
+        modes: Vec<(&'static str, i32, i32, f64)>,
+        current_mode: &'static str,
+    }
+
+    /// State of the mock, and every configuration applied with its method
+    struct Mock {
+        monitors: Vec<MockMonitor>,
+        logical_monitors: Vec<Logical>,
+        applied: Vec<(u32, Vec<Logical>)>,
+    }
+
+    /// `org.gnome.Mutter.DisplayConfig` in the logical layout mode
+    struct DisplayConfig(Arc<Mutex<Mock>>);
+
+    fn properties<'a>(
+        properties: impl IntoIterator<Item = (&'a str, Value<'a>)>,
+    ) -> HashMap<String, OwnedValue> {
+        properties
+            .into_iter()
+            .map(|(key, value)| (key.to_string(), value.try_into().unwrap()))
+            .collect()
+    }
+
+    #[interface(name = "org.gnome.Mutter.DisplayConfig")]
+    impl DisplayConfig {
+        #[allow(clippy::type_complexity)]
+        fn get_current_state(
+            &self,
+        ) -> (
+            u32,
+            Vec<(
+                (String, String, String, String),
+                Vec<(
+                    String,
+                    i32,
+                    i32,
+                    f64,
+                    f64,
+                    Vec<f64>,
+                    HashMap<String, OwnedValue>,
+                )>,
+                HashMap<String, OwnedValue>,
+            )>,
+            Vec<(
+                i32,
+                i32,
+                f64,
+                u32,
+                bool,
+                Vec<(String, String, String, String)>,
+                HashMap<String, OwnedValue>,
+            )>,
+            HashMap<String, OwnedValue>,
+        ) {
+            let mock = self.0.lock().unwrap();
+            let monitors = mock
+                .monitors
+                .iter()
+                .map(|monitor| {
+                    let modes = monitor
+                        .modes
+                        .iter()
+                        .map(|(id, width, height, preferred_scale)| {
+                            (
+                                id.to_string(),
+                                *width,
+                                *height,
+                                60.,
+                                *preferred_scale,
+                                vec![1., 2.],
+                                properties([(
+                                    "is-current",
+                                    Value::from(*id == monitor.current_mode),
+                                )]),
+                            )
+                        })
+                        .collect();
+                    (monitor.spec.clone(), modes, HashMap::new())
+                })
+                .collect();
+            let logical_monitors = mock
+                .logical_monitors
+                .iter()
+                .map(|(x, y, scale, transform, primary, connectors)| {
+                    let specs = connectors
+                        .iter()
+                        .map(|connector| {
+                            mock.monitors
+                                .iter()
+                                .find(|monitor| monitor.spec.0 == *connector)
+                                .unwrap()
+                                .spec
+                                .clone()
+                        })
+                        .collect();
+                    (*x, *y, *scale, *transform, *primary, specs, HashMap::new())
+                })
+                .collect();
+            (
+                1,
+                monitors,
+                logical_monitors,
+                properties([("layout-mode", Value::from(1u32))]),
+            )
+        }
+
+        #[allow(clippy::type_complexity)]
+        fn apply_monitors_config(
+            &self,
+            _serial: u32,
+            method: u32,
+            logical_monitors: Vec<(
+                i32,
+                i32,
+                f64,
+                u32,
+                bool,
+                Vec<(String, String, HashMap<String, OwnedValue>)>,
+            )>,
+            _properties: HashMap<String, OwnedValue>,
+        ) {
+            let mut mock = self.0.lock().unwrap();
+            let mut logical = Vec::new();
+            for (x, y, scale, transform, primary, monitors) in logical_monitors {
+                let mut connectors = Vec::new();
+                for (connector, mode, _) in monitors {
+                    if let Some(monitor) = mock
+                        .monitors
+                        .iter_mut()
+                        .find(|monitor| monitor.spec.0 == connector)
+                        && let Some((id, ..)) = monitor.modes.iter().find(|(id, ..)| *id == mode)
+                    {
+                        monitor.current_mode = id;
+                    }
+                    connectors.push(connector);
+                }
+                logical.push((x, y, scale, transform, primary, connectors));
+            }
+            logical.sort_by_key(|(x, y, ..)| (*x, *y));
+
+            if method != ApplyMethod::Verify as u32 {
+                mock.logical_monitors = logical.clone();
+            }
+            mock.applied.push((method, logical));
+        }
+    }
+
+    /// A laptop panel scaled by 2, covering 1440x900, with a monitor on its right
+    fn mock() -> Arc<Mutex<Mock>> {
+        let spec = |connector: &str, vendor: &str, product: &str, serial: &str| {
+            (
+                connector.to_string(),
+                vendor.to_string(),
+                product.to_string(),
+                serial.to_string(),
+            )
+        };
+        Arc::new(Mutex::new(Mock {
+            monitors: vec![
+                MockMonitor {
+                    spec: spec("MOCK-1", "BOE", "0x0a1b", "0x00000000"),
+                    modes: vec![
+                        ("2880x1800@60", 2880, 1800, 2.),
+                        ("1920x1200@60", 1920, 1200, 1.),
+                    ],
+                    current_mode: "2880x1800@60",
+                },
+                MockMonitor {
+                    spec: spec("MOCK-2", "DEL", "DELL U2720Q", "7RQKR13"),
+                    modes: vec![
+                        ("2560x1440@60", 2560, 1440, 1.),
+                        ("3840x2160@60", 3840, 2160, 2.),
+                    ],
+                    current_mode: "2560x1440@60",
+                },
+            ],
+            logical_monitors: vec![
+                (0, 0, 2., 0, true, vec![String::from("MOCK-1")]),
+                (1440, 0, 1., 0, false, vec![String::from("MOCK-2")]),
+            ],
+            applied: Vec::new(),
+        }))
+    }
+
+    #[test]
+    #[ignore = "needs dbus-daemon"]
+    fn applies_temporarily_and_persists_once_kept_on_a_mock_display_config() {
+        let bus = Bus::start();
+        let mock = mock();
+        let _server = bus
+            .connect()
+            .name(MutterBackend::DESTINATION)
+            .unwrap()
+            .serve_at(MutterBackend::PATH, DisplayConfig(mock.clone()))
+            .unwrap()
+            .build()
+            .unwrap();
+        let backend: Arc<dyn DisplayBackend> = Arc::new(MutterBackend {
+            conn: bus.connect().build().unwrap(),
+            method: ApplyMethod::Temporary,
+        });
+        let monitors = Monitor::get_monitors(&backend).unwrap();
+        let names: Vec<&str> = monitors
+            .iter()
+            .map(|monitor| monitor.name.as_str())
+            .collect();
+        assert_eq!(names, ["BOE 0A1B", "DEL DELL U2720Q 7RQKR13"]);
+
+        // The panel covers 1440x900 of the desktop, right up to the monitor
+        let current = Preset::from_current_config(&monitors).unwrap();
+        assert_eq!(
+            current.layout(),
+            "BOE 0A1B: 0/0,Landscape,2880x1800@60,scale 2,primary\n\
+             DEL DELL U2720Q 7RQKR13: 1440/0,Landscape,2560x1440@60,scale 1"
+        );
+        assert!(current.check(&monitors).unwrap().is_empty());
+
+        let preset: Preset = serde_json::from_str(
+            r#"{
+                "name": "Desk on the left",
+                "keybind": null,
+                "map": {
+                    "BOE 0A1B": { "position": { "x": 0, "y": 0 }, "orientation": "Landscape", "primary": true, "flipped": true },
+                    "DEL DELL U2720Q 7RQKR13": { "placement": { "left-of": "BOE 0A1B" }, "orientation": "Landscape" }
+                }
+            }"#,
+        )
+        .unwrap();
+        preset.apply(&monitors).unwrap();
+        let layout = vec![
+            (0, 0, 1., 0, false, vec![String::from("MOCK-2")]),
+            (2560, 0, 2., 4, true, vec![String::from("MOCK-1")]),
+        ];
+        assert_eq!(
+            mock.lock().unwrap().applied,
+            [(ApplyMethod::Temporary as u32, layout.clone())]
+        );
+        assert!(preset.is_applied(&monitors).unwrap());
+
+        Monitor::persist_settings(&monitors).unwrap();
+        assert_eq!(
+            mock.lock().unwrap().applied.last(),
+            Some(&(ApplyMethod::Persistent as u32, layout))
+        );
+    }
+}
@@ -0,0 +1,510 @@
+use crate::{
+    backend::{DisplayBackend, MonitorDevice},
+    display_settings::{DisplayMode, DisplayOrientation, DisplaySettings, Resolution},
+    edid::Edid,
+    error::ThisError,
+    monitor::{Monitor, MonitorName},
+};
+use color_eyre::eyre::Result;
+use windows::{
+    Win32::{
+        Devices::Display::{
+            DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_HEADER,
+            DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO, DISPLAYCONFIG_SOURCE_DEVICE_NAME,
+            DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QDC_ONLY_ACTIVE_PATHS,
+            QueryDisplayConfig, SDC_ALLOW_CHANGES, SDC_APPLY, SDC_SAVE_TO_DATABASE,
+            SDC_USE_SUPPLIED_DISPLAY_CONFIG, SetDisplayConfig,
+        },
+        Foundation::{ERROR_SUCCESS, WIN32_ERROR},
+        Graphics::Gdi::{
+            CDS_NORESET, CDS_SET_PRIMARY, CDS_TEST, CDS_TYPE, CDS_UPDATEREGISTRY,
+            ChangeDisplaySettingsExW, DEVMODEW, DISP_CHANGE, DISP_CHANGE_BADDUALVIEW,
+            DISP_CHANGE_BADFLAGS, DISP_CHANGE_BADMODE, DISP_CHANGE_BADPARAM,
+            DISP_CHANGE_NOTUPDATED, DISP_CHANGE_RESTART, DISP_CHANGE_SUCCESSFUL,
+            DISPLAY_DEVICE_ATTACHED_TO_DESKTOP, DISPLAY_DEVICE_PRIMARY_DEVICE, DISPLAY_DEVICEW,
+            DISPLAYCONFIG_PATH_MODE_IDX_INVALID, DM_DISPLAYFREQUENCY, DM_DISPLAYORIENTATION,
+            DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION, EDS_ROTATEDMODE, ENUM_CURRENT_SETTINGS,
+            ENUM_DISPLAY_SETTINGS_MODE, ENUM_REGISTRY_SETTINGS, EnumDisplayDevicesW,
+            EnumDisplaySettingsExW,
+        },
+        System::Registry::{HKEY_LOCAL_MACHINE, RRF_RT_REG_BINARY, RegGetValueW},
+        UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME,
+    },
+    core::{PCWSTR, w},
+};
+
+/// Drives the monitors through `EnumDisplayDevicesW` / `ChangeDisplaySettingsExW`,
+/// a monitor's id is the name of the adapter it is plugged on and its name is read from its EDID
+///
+/// GDI can't clone adapters, mirrors are set up afterwards through `SetDisplayConfig`
+#[derive(Debug)]
+pub struct WindowsBackend;
+
+/// Changes making up new settings, every staged mode has been tested
+struct Changes<'a> {
+    /// Devmode and flags to stage for each monitor
+    staged: Vec<(&'a Monitor, DEVMODEW, CDS_TYPE)>,
+    /// Mirrors and the monitor they clone
+    clones: Vec<(&'a Monitor, &'a Monitor)>,
+}
+
+impl WindowsBackend {
+    pub fn get_name(device: &DISPLAY_DEVICEW) -> MonitorName {
+        wide_to_string(&device.DeviceName)
+    }
+
+    /// Reads the EDID of a monitor from the registry key of its device, found from its device interface path
+    /// (`\\?\DISPLAY#DEL40B6#5&2b3f1e0c&0&UID4357#{e6f07b5f-ee97-4a90-b076-33f57bf4eaa7}`)
+    fn get_edid(device_interface: &str) -> Option<Vec<u8>> {
+        let instance = device_interface.strip_prefix(r"\\?\")?;
+        let instance = &instance[..instance.rfind('#')?];
+        let key = string_to_wide(&format!(
+            r"SYSTEM\CurrentControlSet\Enum\{}\Device Parameters",
+            instance.replace('#', r"\")
+        ));
+
+        let mut size = 0;
+        let result = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                PCWSTR(key.as_ptr()),
+                w!("EDID"),
+                RRF_RT_REG_BINARY,
+                None,
+                None,
+                Some(&mut size),
+            )
+        };
+        if result != ERROR_SUCCESS {
+            return None;
+        }
+
+        let mut edid = vec![0u8; size as usize];
+        let result = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                PCWSTR(key.as_ptr()),
+                w!("EDID"),
+                RRF_RT_REG_BINARY,
+                None,
+                Some(edid.as_mut_ptr().cast()),
+                Some(&mut size),
+            )
+        };
+        if result != ERROR_SUCCESS {
+            return None;
+        }
+
+        edid.truncate(size as usize);
+        Some(edid)
+    }
+
+    fn new_display_device() -> DISPLAY_DEVICEW {
+        DISPLAY_DEVICEW {
+            cb: std::mem::size_of::<DISPLAY_DEVICEW>() as u32,
+            ..Default::default()
+        }
+    }
+
+    /// Finds the adapter the monitor is plugged on
+    fn get_adapter(monitor: &Monitor) -> Result<DISPLAY_DEVICEW> {
+        let mut device_index = 0;
+        loop {
+            let mut adapter = Self::new_display_device();
+            let success =
+                unsafe { EnumDisplayDevicesW(PCWSTR::null(), device_index, &mut adapter, 0) };
+
+            if !success.as_bool() {
+                Err(ThisError::MonitorNotFound)?;
+            }
+            if Self::get_name(&adapter) == monitor.id {
+                return Ok(adapter);
+            }
+
+            device_index += 1;
+        }
+    }
+
+    pub fn get_devmodew(monitor: &Monitor) -> Result<DEVMODEW> {
+        Self::enum_settings(monitor, ENUM_CURRENT_SETTINGS)
+    }
+
+    fn enum_settings(monitor: &Monitor, mode: ENUM_DISPLAY_SETTINGS_MODE) -> Result<DEVMODEW> {
+        let mut dm = DEVMODEW {
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            ..Default::default()
+        };
+        let device_name = string_to_wide(&monitor.id);
+
+        unsafe {
+            if !EnumDisplaySettingsExW(PCWSTR(device_name.as_ptr()), mode, &mut dm, EDS_ROTATEDMODE)
+                .as_bool()
+            {
+                Err(ThisError::EnumDisplaySettings)?;
+            }
+        }
+
+        Ok(dm)
+    }
+
+    /// Mode and flags staging the settings of a monitor in the registry, they are only applied by [`Self::commit`]
+    fn get_staged_devmodew(
+        monitor: &Monitor,
+        display_settings: &DisplaySettings,
+    ) -> Result<(DEVMODEW, CDS_TYPE)> {
+        if !display_settings.enabled {
+            // An empty mode detaches the monitor from the desktop
+            let dm = DEVMODEW {
+                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                dmFields: DM_POSITION | DM_PELSWIDTH | DM_PELSHEIGHT,
+                ..Default::default()
+            };
+            return Ok((dm, CDS_UPDATEREGISTRY | CDS_NORESET));
+        }
+
+        // Detached monitors have no current settings, the last ones saved are used instead
+        let mut dm = Self::get_devmodew(monitor)
+            .or_else(|_| Self::enum_settings(monitor, ENUM_REGISTRY_SETTINGS))?;
+
+        let current = DisplaySettings::from(dm);
+        let resolution = match display_settings.resolution.or(current
+            .resolution
+            .filter(|resolution| resolution.width != 0))
+        {
+            Some(resolution) => resolution,
+            // Falls back on the biggest mode, usually the native one
+            None => WindowsBackend
+                .get_modes(monitor)?
+                .into_iter()
+                .map(|mode| mode.resolution)
+                .max()
+                .ok_or(ThisError::EnumDisplaySettings)?,
+        }
+        .oriented(display_settings.orientation);
+
+        dm.dmPelsWidth = resolution.width;
+        dm.dmPelsHeight = resolution.height;
+        if let Some(refresh_rate) = display_settings.refresh_rate {
+            dm.dmDisplayFrequency = refresh_rate;
+        }
+
+        dm.Anonymous1.Anonymous2.dmDisplayOrientation = display_settings.orientation.into();
+        dm.Anonymous1.Anonymous2.dmPosition = display_settings.position.into();
+
+        dm.dmFields |= DM_DISPLAYORIENTATION
+            | DM_PELSWIDTH
+            | DM_PELSHEIGHT
+            | DM_POSITION
+            | DM_DISPLAYFREQUENCY;
+
+        let mut flags = CDS_UPDATEREGISTRY | CDS_NORESET;
+        if display_settings.primary {
+            flags |= CDS_SET_PRIMARY;
+        }
+
+        Ok((dm, flags))
+    }
+
+    /// Makes every mirror share the source of its leader, the first monitor of each pair
+    fn clone_adapters(clones: &[(&Monitor, &Monitor)]) -> Result<()> {
+        let mut path_count = 0;
+        let mut mode_count = 0;
+        unsafe {
+            GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+        }
+        .ok()?;
+
+        let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+        let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+        unsafe {
+            QueryDisplayConfig(
+                QDC_ONLY_ACTIVE_PATHS,
+                &mut path_count,
+                paths.as_mut_ptr(),
+                &mut mode_count,
+                modes.as_mut_ptr(),
+                None,
+            )
+        }
+        .ok()?;
+        paths.truncate(path_count as usize);
+        modes.truncate(mode_count as usize);
+
+        let sources = paths.iter().map(source_name).collect::<Result<Vec<_>>>()?;
+
+        for (mirror, leader) in clones {
+            // Already sharing the same adapter
+            if mirror.id == leader.id {
+                continue;
+            }
+
+            let find_path = |monitor: &Monitor| {
+                sources
+                    .iter()
+                    .position(|source| *source == monitor.id)
+                    .ok_or(ThisError::MonitorNotFound)
+            };
+            let (mirror_path, leader_path) = (find_path(mirror)?, find_path(leader)?);
+
+            if paths[mirror_path].sourceInfo.adapterId != paths[leader_path].sourceInfo.adapterId {
+                Err(ThisError::InvalidMirror(format!(
+                    "{} and {} are not plugged on the same graphics card",
+                    mirror.name, leader.name
+                )))?;
+            }
+
+            paths[mirror_path].sourceInfo = paths[leader_path].sourceInfo;
+            // Lets Windows pick a target mode fitting the shared source
+            paths[mirror_path].targetInfo.Anonymous.modeInfoIdx =
+                DISPLAYCONFIG_PATH_MODE_IDX_INVALID;
+        }
+
+        let result = unsafe {
+            SetDisplayConfig(
+                Some(&paths),
+                Some(&modes),
+                SDC_APPLY
+                    | SDC_USE_SUPPLIED_DISPLAY_CONFIG
+                    | SDC_ALLOW_CHANGES
+                    | SDC_SAVE_TO_DATABASE,
+            )
+        };
+        WIN32_ERROR(result as u32).ok()?;
+
+        Ok(())
+    }
+
+    fn change_settings(monitor: &Monitor, dm: &DEVMODEW, flags: CDS_TYPE) -> Result<()> {
+        let device_name = string_to_wide(&monitor.id);
+        let result = unsafe {
+            ChangeDisplaySettingsExW(PCWSTR(device_name.as_ptr()), Some(dm), None, flags, None)
+        };
+
+        if result == DISP_CHANGE_SUCCESSFUL {
+            Ok(())
+        } else {
+            Err(ThisError::ChangeMonitorSettings {
+                monitor: monitor.name.clone(),
+                reason: String::from(disp_change_reason(result)),
+            })?
+        }
+    }
+
+    /// Applies every staged settings at once, so the primary monitor can move without the others overlapping it in between
+    fn commit() -> Result<()> {
+        let result =
+            unsafe { ChangeDisplaySettingsExW(PCWSTR::null(), None, None, CDS_TYPE(0), None) };
+
+        if result == DISP_CHANGE_SUCCESSFUL {
+            Ok(())
+        } else {
+            Err(ThisError::DispChange(result))?
+        }
+    }
+
+    /// Tests the mode of every listed monitor and sorts them into the devmodes to stage and the mirrors to clone
+    fn prepare_changes<'a>(settings: &[(&'a Monitor, &DisplaySettings)]) -> Result<Changes<'a>> {
+        let mut staged = Vec::new();
+        let mut clones = Vec::new();
+        for (monitor, display_settings) in settings {
+            let leader = display_settings
+                .mirror
+                .as_ref()
+                .filter(|_| display_settings.enabled)
+                .and_then(|leader| settings.iter().find(|(other, _)| other.name == *leader));
+
+            match leader {
+                // Mirrors take the mode of their leader's adapter, staging them would overlap it
+                Some((leader, _)) => clones.push((*monitor, *leader)),
+                None => {
+                    let (dm, flags) = Self::get_staged_devmodew(monitor, display_settings)?;
+                    // Every mode is tested before anything is staged, a bad one is blamed on its monitor
+                    if display_settings.enabled {
+                        Self::change_settings(monitor, &dm, CDS_TEST)?;
+                    }
+                    staged.push((*monitor, dm, flags));
+                }
+            }
+        }
+
+        Ok(Changes { staged, clones })
+    }
+}
+
+impl DisplayBackend for WindowsBackend {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>> {
+        let mut monitors = Vec::new();
+        unsafe {
+            let mut device_index = 0;
+
+            loop {
+                let mut adapter = Self::new_display_device();
+
+                let success = EnumDisplayDevicesW(
+                    PCWSTR::null(),
+                    device_index,
+                    &mut adapter,
+                    EDD_GET_DEVICE_INTERFACE_NAME,
+                );
+
+                if !success.as_bool() {
+                    break;
+                }
+
+                let mut monitor_index = 0;
+                loop {
+                    let mut monitor = Self::new_display_device();
+
+                    let success = EnumDisplayDevicesW(
+                        PCWSTR(adapter.DeviceName.as_ptr()),
+                        monitor_index,
+                        &mut monitor,
+                        EDD_GET_DEVICE_INTERFACE_NAME,
+                    );
+
+                    if !success.as_bool() {
+                        break;
+                    }
+
+                    monitor_index += 1;
+
+                    // The device interface path survives renumbering, but not being plugged in another port
+                    let device_interface = wide_to_string(&monitor.DeviceID);
+                    let edid =
+                        Self::get_edid(&device_interface).and_then(|edid| Edid::parse(&edid).ok());
+                    let name = edid
+                        .as_ref()
+                        .and_then(Edid::stable_name)
+                        .or((!device_interface.is_empty()).then(|| device_interface.clone()))
+                        .unwrap_or(Self::get_name(&monitor));
+
+                    monitors.push(MonitorDevice {
+                        name,
+                        id: Self::get_name(&adapter),
+                        legacy_name: Some(Self::get_name(&monitor)),
+                        edid,
+                    });
+                }
+
+                device_index += 1;
+            }
+        }
+        Ok(monitors)
+    }
+
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings> {
+        let state = Self::get_adapter(monitor)?.StateFlags;
+        if !state.contains(DISPLAY_DEVICE_ATTACHED_TO_DESKTOP) {
+            return Ok(DisplaySettings::disabled());
+        }
+
+        let dm = Self::get_devmodew(monitor)?;
+        Ok(DisplaySettings {
+            primary: state.contains(DISPLAY_DEVICE_PRIMARY_DEVICE),
+            ..dm.into()
+        })
+    }
+
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>> {
+        let device_name = string_to_wide(&monitor.id);
+        let mut modes = Vec::new();
+
+        let mut mode_index = 0;
+        loop {
+            let mut dm = DEVMODEW {
+                dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+                ..Default::default()
+            };
+
+            let success = unsafe {
+                EnumDisplaySettingsExW(
+                    PCWSTR(device_name.as_ptr()),
+                    ENUM_DISPLAY_SETTINGS_MODE(mode_index),
+                    &mut dm,
+                    // Lists the modes of every orientation rather than only the current one
+                    EDS_ROTATEDMODE,
+                )
+            };
+
+            if !success.as_bool() {
+                break;
+            }
+
+            mode_index += 1;
+
+            // Rotated modes have their width and height swapped, resolutions are compared in landscape
+            let orientation: DisplayOrientation =
+                unsafe { dm.Anonymous1.Anonymous2.dmDisplayOrientation }.into();
+            modes.push(DisplayMode {
+                resolution: Resolution {
+                    width: dm.dmPelsWidth,
+                    height: dm.dmPelsHeight,
+                }
+                .oriented(orientation),
+                refresh_rate: dm.dmDisplayFrequency,
+                bits_per_pixel: Some(dm.dmBitsPerPel),
+            });
+        }
+
+        Ok(modes)
+    }
+
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        let Changes { staged, clones } = Self::prepare_changes(settings)?;
+
+        for (monitor, dm, flags) in staged {
+            Self::change_settings(monitor, &dm, flags)?;
+        }
+        Self::commit()?;
+        if !clones.is_empty() {
+            Self::clone_adapters(&clones)?;
+        }
+
+        Ok(())
+    }
+
+    /// Every mode is tested with `CDS_TEST`, nothing is staged
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        Self::prepare_changes(settings)?;
+        Ok(())
+    }
+}
+
+fn disp_change_reason(result: DISP_CHANGE) -> &'static str {
+    match result {
+        DISP_CHANGE_BADDUALVIEW => "the system is DualView capable",
+        DISP_CHANGE_BADFLAGS => "invalid flags",
+        DISP_CHANGE_BADMODE => "the graphics mode is not supported",
+        DISP_CHANGE_BADPARAM => "invalid parameter",
+        DISP_CHANGE_NOTUPDATED => "unable to write settings to the registry",
+        DISP_CHANGE_RESTART => "the computer must be restarted",
+        _ => "the display driver failed the graphics mode",
+    }
+}
+
+/// GDI name of the adapter a path starts from, which is the id of its monitors
+fn source_name(path: &DISPLAYCONFIG_PATH_INFO) -> Result<String> {
+    let mut name = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+        header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+            r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+            size: std::mem::size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+            adapterId: path.sourceInfo.adapterId,
+            id: path.sourceInfo.id,
+        },
+        ..Default::default()
+    };
+
+    let result = unsafe { DisplayConfigGetDeviceInfo(&mut name.header) };
+    WIN32_ERROR(result as u32).ok()?;
+
+    Ok(wide_to_string(&name.viewGdiDeviceName))
+}
+
+fn wide_to_string(wide: &[u16]) -> String {
+    let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
+    String::from_utf16_lossy(&wide[..len])
+}
+
+fn string_to_wide(s: &str) -> Vec<u16> {
+    s.encode_utf16().chain(std::iter::once(0)).collect()
+}
@@ -0,0 +1,591 @@
+use crate::{
+    backend::{DisplayBackend, MonitorDevice},
+    display_settings::{DisplayMode, DisplayOrientation, DisplaySettings, Pos, Resolution},
+    error::ThisError,
+    monitor::Monitor,
+};
+use color_eyre::eyre::Result;
+use wayland_client::{
+    Connection, Dispatch, EventQueue, QueueHandle, WEnum, event_created_child,
+    globals::{GlobalListContents, registry_queue_init},
+    protocol::{wl_output::Transform, wl_registry::WlRegistry},
+};
+use wayland_protocols_wlr::output_management::v1::client::{
+    zwlr_output_configuration_head_v1::ZwlrOutputConfigurationHeadV1,
+    zwlr_output_configuration_v1::{self, ZwlrOutputConfigurationV1},
+    zwlr_output_head_v1::{self, ZwlrOutputHeadV1},
+    zwlr_output_manager_v1::{self, ZwlrOutputManagerV1},
+    zwlr_output_mode_v1::{self, ZwlrOutputModeV1},
+};
+
+/// Drives the heads of a wlroots based compositor (sway, river, labwc...) through
+/// `wlr-output-management-unstable-v1`, a monitor's id is its connector name and its name is read from its EDID, see [`MonitorDevice::from_connector`]
+///
+/// Positions are in the scaled pixels of the desktop, as are the areas covered by the monitors, see [`Resolution::scaled`].
+/// The protocol has no notion of a primary output, the `primary` flag is only honored through the position:
+/// presets put their primary monitor at 0,0, and the monitor at 0,0 is read as the primary one
+#[derive(Debug)]
+pub struct WlrBackend {
+    conn: Connection,
+}
+
+#[derive(Debug)]
+struct Head {
+    proxy: ZwlrOutputHeadV1,
+    name: String,
+    /// Make, model and serial number, sent from version 2 on
+    make: String,
+    model: String,
+    serial_number: String,
+    enabled: bool,
+    position: Pos,
+    orientation: DisplayOrientation,
+    flipped: bool,
+    scale: f64,
+    modes: Vec<ZwlrOutputModeV1>,
+    current_mode: Option<ZwlrOutputModeV1>,
+}
+
+#[derive(Debug)]
+struct HeadMode {
+    proxy: ZwlrOutputModeV1,
+    resolution: Resolution,
+    /// Refresh rate in mHz
+    refresh: i32,
+    preferred: bool,
+}
+
+impl HeadMode {
+    fn refresh_rate(&self) -> u32 {
+        (self.refresh as f64 / 1000.).round() as u32
+    }
+}
+
+/// Output manager state, rebuilt for every call as heads are bound to the event queue they were received on
+#[derive(Debug, Default)]
+struct State {
+    heads: Vec<Head>,
+    modes: Vec<HeadMode>,
+    serial: Option<u32>,
+    outcome: Option<std::result::Result<(), &'static str>>,
+}
+
+/// Transforms are counted counterclockwise by wayland, `Portrait` being a monitor turned to the left
+fn transform(orientation: DisplayOrientation, flipped: bool) -> Transform {
+    match (orientation, flipped) {
+        (DisplayOrientation::Landscape, false) => Transform::Normal,
+        (DisplayOrientation::Portrait, false) => Transform::_90,
+        (DisplayOrientation::LandscapeReversed, false) => Transform::_180,
+        (DisplayOrientation::PortraitReversed, false) => Transform::_270,
+        (DisplayOrientation::Landscape, true) => Transform::Flipped,
+        (DisplayOrientation::Portrait, true) => Transform::Flipped90,
+        (DisplayOrientation::LandscapeReversed, true) => Transform::Flipped180,
+        (DisplayOrientation::PortraitReversed, true) => Transform::Flipped270,
+    }
+}
+
+fn is_flipped(transform: Transform) -> bool {
+    matches!(
+        transform,
+        Transform::Flipped | Transform::Flipped90 | Transform::Flipped180 | Transform::Flipped270
+    )
+}
+
+impl From<Transform> for DisplayOrientation {
+    fn from(value: Transform) -> Self {
+        match value {
+            Transform::_90 | Transform::Flipped90 => DisplayOrientation::Portrait,
+            Transform::_180 | Transform::Flipped180 => DisplayOrientation::LandscapeReversed,
+            Transform::_270 | Transform::Flipped270 => DisplayOrientation::PortraitReversed,
+            _ => DisplayOrientation::Landscape,
+        }
+    }
+}
+
+impl WlrBackend {
+    /// Connects to the compositor pointed to by the `WAYLAND_DISPLAY` env var
+    pub fn connect() -> Result<Self> {
+        Ok(Self {
+            conn: Connection::connect_to_env()?,
+        })
+    }
+
+    /// Binds the output manager and waits for the current state of every head
+    fn get_state(&self) -> Result<(EventQueue<State>, ZwlrOutputManagerV1, State)> {
+        let (globals, mut queue) = registry_queue_init::<State>(&self.conn)?;
+        let manager: ZwlrOutputManagerV1 = globals.bind(&queue.handle(), 1..=4, ())?;
+
+        let mut state = State::default();
+        while state.serial.is_none() {
+            queue.blocking_dispatch(&mut state)?;
+        }
+
+        Ok((queue, manager, state))
+    }
+
+    fn current_mode<'a>(state: &'a State, head: &Head) -> Option<&'a HeadMode> {
+        let current = head.current_mode.as_ref()?;
+        state.modes.iter().find(|mode| mode.proxy == *current)
+    }
+
+    fn preferred_mode<'a>(state: &'a State, head: &Head) -> Option<&'a HeadMode> {
+        state
+            .modes
+            .iter()
+            .find(|mode| mode.preferred && head.modes.contains(&mode.proxy))
+    }
+
+    /// Finds the mode of a head matching a resolution and, if any, a refresh rate
+    fn find_mode<'a>(
+        state: &'a State,
+        head: &Head,
+        resolution: Resolution,
+        refresh_rate: Option<u32>,
+    ) -> Option<&'a HeadMode> {
+        state
+            .modes
+            .iter()
+            .filter(|mode| head.modes.contains(&mode.proxy))
+            .filter(|mode| mode.resolution == resolution)
+            .filter(|mode| {
+                refresh_rate.is_none_or(|refresh_rate| mode.refresh_rate() == refresh_rate)
+            })
+            .max_by_key(|mode| mode.refresh)
+    }
+
+    fn release(&self, manager: &ZwlrOutputManagerV1) -> Result<()> {
+        manager.stop();
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    /// Sends the configuration of the settings, only tested by the compositor when `test` is set
+    fn configure(&self, settings: &[(&Monitor, &DisplaySettings)], test: bool) -> Result<()> {
+        let (mut queue, manager, mut state) = self.get_state()?;
+
+        for (monitor, _) in settings {
+            if !state.heads.iter().any(|head| head.name == monitor.id) {
+                self.release(&manager)?;
+                Err(ThisError::MonitorNotFound)?;
+            }
+        }
+
+        // Every head has to be part of the configuration, the ones not listed keep their current state
+        let configuration =
+            manager.create_configuration(state.serial.unwrap(), &queue.handle(), ());
+        for head in &state.heads {
+            let target = settings
+                .iter()
+                .find(|(monitor, _)| monitor.id == head.name)
+                .map(|(_, settings)| *settings);
+
+            match target {
+                Some(settings) if !settings.enabled => configuration.disable_head(&head.proxy),
+                Some(settings) => {
+                    let config_head = configuration.enable_head(&head.proxy, &queue.handle(), ());
+                    config_head.set_position(settings.position.x, settings.position.y);
+                    config_head.set_transform(transform(settings.orientation, settings.flipped));
+                    if let Some(scale) = settings.scale {
+                        config_head.set_scale(scale);
+                    }
+
+                    if settings.resolution.is_some() || settings.refresh_rate.is_some() {
+                        // Turned off heads have no current mode, their preferred one is used instead
+                        let current = Self::current_mode(&state, head)
+                            .or_else(|| Self::preferred_mode(&state, head));
+                        let resolution = settings
+                            .resolution
+                            .or(current.map(|mode| mode.resolution))
+                            .ok_or(ThisError::EnumDisplaySettings)?;
+                        let refresh_rate = settings
+                            .refresh_rate
+                            .or(current.map(HeadMode::refresh_rate));
+
+                        match Self::find_mode(&state, head, resolution, refresh_rate) {
+                            Some(mode) => config_head.set_mode(&mode.proxy),
+                            None => config_head.set_custom_mode(
+                                resolution.width as i32,
+                                resolution.height as i32,
+                                refresh_rate.unwrap_or_default() as i32 * 1000,
+                            ),
+                        }
+                    } else if !head.enabled
+                        && let Some(mode) = Self::preferred_mode(&state, head)
+                    {
+                        config_head.set_mode(&mode.proxy);
+                    }
+                }
+                None if head.enabled => {
+                    configuration.enable_head(&head.proxy, &queue.handle(), ());
+                }
+                None => configuration.disable_head(&head.proxy),
+            }
+        }
+        if test {
+            configuration.test();
+        } else {
+            configuration.apply();
+        }
+
+        while state.outcome.is_none() {
+            queue.blocking_dispatch(&mut state)?;
+        }
+        configuration.destroy();
+        self.release(&manager)?;
+
+        state
+            .outcome
+            .unwrap()
+            .map_err(|reason| ThisError::ChangeSettings(reason.to_string()).into())
+    }
+}
+
+impl DisplayBackend for WlrBackend {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>> {
+        let (_, manager, state) = self.get_state()?;
+        self.release(&manager)?;
+
+        Ok(state
+            .heads
+            .into_iter()
+            .map(|head| {
+                MonitorDevice::from_connector(
+                    &head.name,
+                    &head.make,
+                    &head.model,
+                    &head.serial_number,
+                )
+            })
+            .collect())
+    }
+
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings> {
+        let (_, manager, state) = self.get_state()?;
+        self.release(&manager)?;
+
+        let head = state
+            .heads
+            .iter()
+            .find(|head| head.name == monitor.id)
+            .ok_or(ThisError::EnumDisplaySettings)?;
+        if !head.enabled {
+            return Ok(DisplaySettings::disabled());
+        }
+
+        let mode = Self::current_mode(&state, head);
+
+        Ok(DisplaySettings {
+            position: head.position,
+            placement: None,
+            orientation: head.orientation,
+            resolution: mode.map(|mode| mode.resolution),
+            refresh_rate: mode.map(HeadMode::refresh_rate),
+            scale: Some(head.scale),
+            flipped: head.flipped,
+            primary: head.position == Pos::default(),
+            enabled: true,
+            mirror: None,
+        })
+    }
+
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>> {
+        let (_, manager, state) = self.get_state()?;
+        self.release(&manager)?;
+
+        let head = state
+            .heads
+            .iter()
+            .find(|head| head.name == monitor.id)
+            .ok_or(ThisError::MonitorNotFound)?;
+
+        Ok(state
+            .modes
+            .iter()
+            .filter(|mode| head.modes.contains(&mode.proxy))
+            .map(|mode| DisplayMode {
+                resolution: mode.resolution,
+                refresh_rate: mode.refresh_rate(),
+                bits_per_pixel: None,
+            })
+            .collect())
+    }
+
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        self.configure(settings, false)
+    }
+
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        self.configure(settings, true)
+    }
+}
+
+impl Dispatch<WlRegistry, GlobalListContents> for State {
+    fn event(
+        _: &mut Self,
+        _: &WlRegistry,
+        _: <WlRegistry as wayland_client::Proxy>::Event,
+        _: &GlobalListContents,
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+impl Dispatch<ZwlrOutputManagerV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrOutputManagerV1,
+        event: zwlr_output_manager_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        match event {
+            zwlr_output_manager_v1::Event::Head { head } => state.heads.push(Head {
+                proxy: head,
+                name: String::new(),
+                make: String::new(),
+                model: String::new(),
+                serial_number: String::new(),
+                enabled: false,
+                position: Pos { x: 0, y: 0 },
+                orientation: DisplayOrientation::Landscape,
+                flipped: false,
+                scale: 1.,
+                modes: Vec::new(),
+                current_mode: None,
+            }),
+            zwlr_output_manager_v1::Event::Done { serial } => state.serial = Some(serial),
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrOutputManagerV1, [
+        zwlr_output_manager_v1::EVT_HEAD_OPCODE => (ZwlrOutputHeadV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputHeadV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputHeadV1,
+        event: zwlr_output_head_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(head) = state.heads.iter_mut().find(|head| head.proxy == *proxy) else {
+            return;
+        };
+
+        match event {
+            zwlr_output_head_v1::Event::Name { name } => head.name = name,
+            zwlr_output_head_v1::Event::Make { make } => head.make = make,
+            zwlr_output_head_v1::Event::Model { model } => head.model = model,
+            zwlr_output_head_v1::Event::SerialNumber { serial_number } => {
+                head.serial_number = serial_number
+            }
+            zwlr_output_head_v1::Event::Enabled { enabled } => head.enabled = enabled != 0,
+            zwlr_output_head_v1::Event::Position { x, y } => head.position = Pos { x, y },
+            zwlr_output_head_v1::Event::Transform {
+                transform: WEnum::Value(transform),
+            } => {
+                head.orientation = transform.into();
+                head.flipped = is_flipped(transform);
+            }
+            zwlr_output_head_v1::Event::Scale { scale } => head.scale = scale,
+            zwlr_output_head_v1::Event::Mode { mode } => {
+                state.modes.push(HeadMode {
+                    proxy: mode.clone(),
+                    resolution: Resolution {
+                        width: 0,
+                        height: 0,
+                    },
+                    refresh: 0,
+                    preferred: false,
+                });
+                head.modes.push(mode);
+            }
+            zwlr_output_head_v1::Event::CurrentMode { mode } => head.current_mode = Some(mode),
+            zwlr_output_head_v1::Event::Finished => {
+                state.heads.retain(|head| head.proxy != *proxy);
+            }
+            _ => {}
+        }
+    }
+
+    event_created_child!(State, ZwlrOutputHeadV1, [
+        zwlr_output_head_v1::EVT_MODE_OPCODE => (ZwlrOutputModeV1, ()),
+    ]);
+}
+
+impl Dispatch<ZwlrOutputModeV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        proxy: &ZwlrOutputModeV1,
+        event: zwlr_output_mode_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        let Some(mode) = state.modes.iter_mut().find(|mode| mode.proxy == *proxy) else {
+            return;
+        };
+
+        match event {
+            zwlr_output_mode_v1::Event::Size { width, height } => {
+                mode.resolution = Resolution {
+                    width: width as u32,
+                    height: height as u32,
+                }
+            }
+            zwlr_output_mode_v1::Event::Refresh { refresh } => mode.refresh = refresh,
+            zwlr_output_mode_v1::Event::Preferred => mode.preferred = true,
+            zwlr_output_mode_v1::Event::Finished => {
+                state.modes.retain(|mode| mode.proxy != *proxy);
+            }
+            _ => {}
+        }
+    }
+}
+
+impl Dispatch<ZwlrOutputConfigurationV1, ()> for State {
+    fn event(
+        state: &mut Self,
+        _: &ZwlrOutputConfigurationV1,
+        event: zwlr_output_configuration_v1::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+        state.outcome = match event {
+            zwlr_output_configuration_v1::Event::Succeeded => Some(Ok(())),
+            zwlr_output_configuration_v1::Event::Failed => {
+                Some(Err("the compositor refused the configuration"))
+            }
+            zwlr_output_configuration_v1::Event::Cancelled => {
+                Some(Err("the outputs changed while applying the configuration"))
+            }
+            _ => state.outcome,
+        };
+    }
+}
+
+impl Dispatch<ZwlrOutputConfigurationHeadV1, ()> for State {
+    fn event(
+        _: &mut Self,
+        _: &ZwlrOutputConfigurationHeadV1,
+        _: <ZwlrOutputConfigurationHeadV1 as wayland_client::Proxy>::Event,
+        _: &(),
+        _: &Connection,
+        _: &QueueHandle<Self>,
+    ) {
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{backend::DisplayBackend, preset::Preset};
+    use std::{
+        fs,
+        os::unix::net::UnixStream,
+        path::PathBuf,
+        process::{Child, Command},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// Headless sway with two outputs, stopped once dropped
+    struct Sway {
+        process: Child,
+        runtime_dir: PathBuf,
+    }
+
+    impl Sway {
+        fn start() -> Self {
+            let runtime_dir =
+                std::env::temp_dir().join(format!("wimlam-sway-{}", std::process::id()));
+            fs::create_dir_all(&runtime_dir).unwrap();
+            let process = Command::new("sway")
+                .args(["--config", "/dev/null"])
+                .env("XDG_RUNTIME_DIR", &runtime_dir)
+                .env("WLR_BACKENDS", "headless")
+                .env("WLR_HEADLESS_OUTPUTS", "2")
+                .env("WLR_LIBINPUT_NO_DEVICES", "1")
+                .env("WLR_RENDERER", "pixman")
+                .env_remove("WAYLAND_DISPLAY")
+                .env_remove("DISPLAY")
+                .spawn()
+                .expect("sway must be installed to run this test");
+            Self {
+                process,
+                runtime_dir,
+            }
+        }
+
+        fn backend(&self) -> WlrBackend {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let socket = fs::read_dir(&self.runtime_dir)
+                    .unwrap()
+                    .flatten()
+                    .map(|entry| entry.path())
+                    .find(|path| {
+                        path.extension().is_none() && path.to_string_lossy().contains("wayland-")
+                    });
+                if let Some(stream) = socket.and_then(|socket| UnixStream::connect(socket).ok()) {
+                    return WlrBackend {
+                        conn: Connection::from_socket(stream).unwrap(),
+                    };
+                }
+                assert!(Instant::now() < deadline, "sway didn't start");
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    impl Drop for Sway {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+            let _ = fs::remove_dir_all(&self.runtime_dir);
+        }
+    }
+
+    #[test]
+    #[ignore = "needs sway"]
+    fn applies_scaled_and_flipped_outputs_on_headless_sway() {
+        let sway = Sway::start();
+        let backend: Arc<dyn DisplayBackend> = Arc::new(sway.backend());
+        let monitors = Monitor::get_monitors(&backend).unwrap();
+        assert_eq!(monitors.len(), 2);
+
+        let first = backend.get_display_settings(&monitors[0]).unwrap();
+        let resolution = first.resolution.unwrap();
+        let second = DisplaySettings {
+            position: Pos {
+                x: resolution.width as i32,
+                y: 0,
+            },
+            scale: Some(2.),
+            flipped: true,
+            orientation: DisplayOrientation::Portrait,
+            ..backend.get_display_settings(&monitors[1]).unwrap()
+        };
+        backend.apply(&[(&monitors[1], &second)]).unwrap();
+
+        let applied = backend.get_display_settings(&monitors[1]).unwrap();
+        assert_eq!(applied.position, second.position);
+        assert_eq!(applied.scale, Some(2.));
+        assert!(applied.flipped);
+        assert_eq!(applied.orientation, DisplayOrientation::Portrait);
+        assert!(first.primary && !applied.primary);
+
+        // Captured back with its scale, the layout has no overlap nor gap
+        let preset = Preset::from_current_config(&monitors).unwrap();
+        assert!(preset.check(&monitors).unwrap().is_empty());
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+}
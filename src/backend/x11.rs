@@ -0,0 +1,723 @@
+use crate::{
+    backend::{DisplayBackend, MonitorDevice, Origin},
+    display_settings::{DisplayMode, DisplayOrientation, DisplaySettings, Pos, Resolution},
+    edid::Edid,
+    error::ThisError,
+    monitor::Monitor,
+};
+use color_eyre::eyre::Result;
+use std::collections::BTreeMap;
+use x11rb::{
+    CURRENT_TIME,
+    connection::Connection as _,
+    protocol::{
+        randr::{
+            Connection, ConnectionExt as _, Crtc, GetCrtcInfoReply, GetOutputInfoReply, Mode,
+            ModeFlag, ModeInfo, Output, Rotation, SetConfig,
+        },
+        xproto::{AtomEnum, ConnectionExt as _, Timestamp, Window},
+    },
+    rust_connection::RustConnection,
+};
+
+/// Drives the outputs of an X11 screen through RandR, a monitor's id is its output XID
+/// and its name is read from its EDID
+///
+/// RandR has no negative coordinates, layouts are translated so their top left corner sits at 0,0
+#[derive(Debug)]
+pub struct X11Backend {
+    conn: RustConnection,
+    screen_num: usize,
+    root: Window,
+}
+
+/// Geometry of an enabled CRTC, as it is or as it will be once applied
+#[derive(Debug, Clone, PartialEq)]
+struct CrtcConfig {
+    x: i32,
+    y: i32,
+    mode: u32,
+    rotation: Rotation,
+    outputs: Vec<Output>,
+    /// Size of the mode, before rotation
+    mode_size: (u16, u16),
+}
+
+/// CRTC configurations taking the screen from its current layout to a new one
+#[derive(Debug)]
+struct Plan {
+    config_timestamp: Timestamp,
+    current: BTreeMap<Crtc, CrtcConfig>,
+    target: BTreeMap<Crtc, CrtcConfig>,
+    primary: Option<Output>,
+}
+
+impl CrtcConfig {
+    fn size(&self) -> (i32, i32) {
+        let (width, height) = (self.mode_size.0 as i32, self.mode_size.1 as i32);
+        if self.rotation == Rotation::ROTATE90 || self.rotation == Rotation::ROTATE270 {
+            (height, width)
+        } else {
+            (width, height)
+        }
+    }
+
+    /// Whether both CRTCs scan out the same part of the screen in the same way
+    fn same_picture(&self, other: &Self) -> bool {
+        self.x == other.x
+            && self.y == other.y
+            && self.mode == other.mode
+            && self.rotation == other.rotation
+    }
+
+    fn fits(&self, width: i32, height: i32) -> bool {
+        let (w, h) = self.size();
+        self.x + w <= width && self.y + h <= height
+    }
+}
+
+/// Rotations are counted counterclockwise by RandR, `Portrait` being a monitor turned to the left
+impl From<DisplayOrientation> for Rotation {
+    fn from(value: DisplayOrientation) -> Self {
+        match value {
+            DisplayOrientation::Landscape => Rotation::ROTATE0,
+            DisplayOrientation::Portrait => Rotation::ROTATE90,
+            DisplayOrientation::LandscapeReversed => Rotation::ROTATE180,
+            DisplayOrientation::PortraitReversed => Rotation::ROTATE270,
+        }
+    }
+}
+
+impl From<Rotation> for DisplayOrientation {
+    fn from(value: Rotation) -> Self {
+        if value.contains(Rotation::ROTATE90) {
+            DisplayOrientation::Portrait
+        } else if value.contains(Rotation::ROTATE180) {
+            DisplayOrientation::LandscapeReversed
+        } else if value.contains(Rotation::ROTATE270) {
+            DisplayOrientation::PortraitReversed
+        } else {
+            DisplayOrientation::Landscape
+        }
+    }
+}
+
+impl X11Backend {
+    /// Connects to the display pointed to by the `DISPLAY` env var
+    pub fn connect() -> Result<Self> {
+        let (conn, screen_num) = x11rb::connect(None)?;
+        let root = conn.setup().roots[screen_num].root;
+        Ok(Self {
+            conn,
+            screen_num,
+            root,
+        })
+    }
+
+    fn get_output_info(&self, monitor: &Monitor) -> Result<GetOutputInfoReply> {
+        let output: Output = monitor.id.parse()?;
+        Ok(self
+            .conn
+            .randr_get_output_info(output, CURRENT_TIME)?
+            .reply()?)
+    }
+
+    /// EDID of the monitor plugged in the output, when the driver exposes it
+    fn get_edid(&self, output: Output) -> Result<Option<Vec<u8>>> {
+        let atom = self.conn.intern_atom(true, b"EDID")?.reply()?.atom;
+        if atom == x11rb::NONE {
+            return Ok(None);
+        }
+
+        let property = self
+            .conn
+            .randr_get_output_property(output, atom, AtomEnum::ANY, 0, 256, false, false)?
+            .reply()?;
+        Ok((property.format == 8 && !property.data.is_empty()).then_some(property.data))
+    }
+
+    fn get_crtc_info(&self, crtc: Crtc, config_timestamp: Timestamp) -> Result<GetCrtcInfoReply> {
+        Ok(self
+            .conn
+            .randr_get_crtc_info(crtc, config_timestamp)?
+            .reply()?)
+    }
+
+    fn set_crtc_config(
+        &self,
+        crtc: Crtc,
+        config_timestamp: Timestamp,
+        config: Option<&CrtcConfig>,
+    ) -> Result<()> {
+        let reply = match config {
+            Some(config) => self.conn.randr_set_crtc_config(
+                crtc,
+                CURRENT_TIME,
+                config_timestamp,
+                config.x as i16,
+                config.y as i16,
+                config.mode,
+                config.rotation,
+                &config.outputs,
+            )?,
+            None => self.conn.randr_set_crtc_config(
+                crtc,
+                CURRENT_TIME,
+                config_timestamp,
+                0,
+                0,
+                x11rb::NONE,
+                Rotation::ROTATE0,
+                &[],
+            )?,
+        }
+        .reply()?;
+
+        if reply.status == SetConfig::SUCCESS {
+            return Ok(());
+        }
+
+        let reason = format!(
+            "RandR refused the configuration of CRTC {crtc} ({:?})",
+            reply.status
+        );
+        match config {
+            Some(config) => {
+                let mut names = Vec::new();
+                for output in &config.outputs {
+                    let info = self
+                        .conn
+                        .randr_get_output_info(*output, CURRENT_TIME)?
+                        .reply()?;
+                    names.push(String::from_utf8_lossy(&info.name).into_owned());
+                }
+
+                Err(ThisError::ChangeMonitorSettings {
+                    monitor: names.join(", "),
+                    reason,
+                })?
+            }
+            None => Err(ThisError::ChangeSettings(reason))?,
+        }
+    }
+
+    /// Size of the screen holding the CRTCs, within the range the screen supports
+    fn screen_size(&self, target: &BTreeMap<Crtc, CrtcConfig>) -> Result<(i32, i32)> {
+        let (width, height) = target.values().fold((0, 0), |(width, height), config| {
+            let (w, h) = config.size();
+            (width.max(config.x + w), height.max(config.y + h))
+        });
+
+        let range = self.conn.randr_get_screen_size_range(self.root)?.reply()?;
+        if width > range.max_width as i32 || height > range.max_height as i32 {
+            Err(ThisError::ChangeSettings(format!(
+                "a {width}x{height} layout is bigger than the screen can be ({}x{})",
+                range.max_width, range.max_height
+            )))?;
+        }
+
+        Ok((
+            width.max(range.min_width as i32),
+            height.max(range.min_height as i32),
+        ))
+    }
+
+    fn apply_plan(&self, plan: &Plan) -> Result<()> {
+        let Plan {
+            config_timestamp,
+            current,
+            target,
+            primary,
+        } = plan;
+        let (width, height) = self.screen_size(target)?;
+
+        // Disable the CRTCs turned off and the ones that would not fit in the new screen size, as xrandr does
+        for (crtc, config) in current {
+            let disable = match target.get(crtc) {
+                Some(target) => target != config && !config.fits(width, height),
+                None => true,
+            };
+            if disable {
+                self.set_crtc_config(*crtc, *config_timestamp, None)?;
+            }
+        }
+
+        let screen = &self.conn.setup().roots[self.screen_num];
+        let mm_width = screen.width_in_millimeters as i32 * width / screen.width_in_pixels as i32;
+        let mm_height =
+            screen.height_in_millimeters as i32 * height / screen.height_in_pixels as i32;
+        self.conn
+            .randr_set_screen_size(
+                self.root,
+                width as u16,
+                height as u16,
+                mm_width as u32,
+                mm_height as u32,
+            )?
+            .check()?;
+
+        for (crtc, config) in target {
+            if current.get(crtc) != Some(config) {
+                self.set_crtc_config(*crtc, *config_timestamp, Some(config))?;
+            }
+        }
+
+        if let Some(primary) = primary {
+            self.conn
+                .randr_set_output_primary(self.root, *primary)?
+                .check()?;
+        }
+
+        Ok(())
+    }
+
+    /// Works out the CRTC configurations of the settings without changing anything
+    fn plan(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<Plan> {
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?;
+
+        let mut current = BTreeMap::new();
+        for crtc in &resources.crtcs {
+            let info = self.get_crtc_info(*crtc, resources.config_timestamp)?;
+            if info.mode == x11rb::NONE {
+                continue;
+            }
+
+            let mode = resources
+                .modes
+                .iter()
+                .find(|mode| mode.id == info.mode)
+                .ok_or(ThisError::EnumDisplaySettings)?;
+
+            current.insert(
+                *crtc,
+                CrtcConfig {
+                    x: info.x as i32,
+                    y: info.y as i32,
+                    mode: info.mode,
+                    rotation: Rotation::from(u16::from(info.rotation) & 0b1111),
+                    outputs: info.outputs,
+                    mode_size: (mode.width, mode.height),
+                },
+            );
+        }
+
+        // Listed outputs are taken off their CRTC first, then put back on one matching their new configuration
+        let mut primary = None;
+        let mut target = current.clone();
+        let mut outputs = Vec::new();
+        for (monitor, display_settings) in settings {
+            let id: Output = monitor.id.parse()?;
+            let output = self.get_output_info(monitor)?;
+
+            let base = target.get(&output.crtc).cloned();
+            for config in target.values_mut() {
+                config.outputs.retain(|output| *output != id);
+            }
+            target.retain(|_, config| !config.outputs.is_empty());
+
+            if !display_settings.enabled {
+                continue;
+            }
+
+            if display_settings.primary {
+                primary = Some(id);
+            }
+
+            let mut config = match base {
+                Some(config) => config,
+                // Turned off outputs start on their preferred mode
+                None => {
+                    let mode = output
+                        .modes
+                        .first()
+                        .and_then(|id| resources.modes.iter().find(|mode| mode.id == *id))
+                        .ok_or(ThisError::EnumDisplaySettings)?;
+
+                    CrtcConfig {
+                        x: 0,
+                        y: 0,
+                        mode: mode.id,
+                        rotation: Rotation::ROTATE0,
+                        outputs: Vec::new(),
+                        mode_size: (mode.width, mode.height),
+                    }
+                }
+            };
+            config.outputs = vec![id];
+
+            if display_settings.resolution.is_some() || display_settings.refresh_rate.is_some() {
+                let current_mode = resources.modes.iter().find(|mode| mode.id == config.mode);
+                let mode = find_mode(
+                    &resources.modes,
+                    &output.modes,
+                    display_settings.resolution.unwrap_or(Resolution {
+                        width: config.mode_size.0 as u32,
+                        height: config.mode_size.1 as u32,
+                    }),
+                    display_settings
+                        .refresh_rate
+                        .or(current_mode.map(refresh_rate)),
+                )
+                .ok_or_else(|| ThisError::ChangeMonitorSettings {
+                    monitor: monitor.name.clone(),
+                    reason: String::from("no such mode"),
+                })?;
+
+                config.mode = mode.id;
+                config.mode_size = (mode.width, mode.height);
+            }
+
+            config.x = display_settings.position.x;
+            config.y = display_settings.position.y;
+            config.rotation = display_settings.orientation.into();
+
+            outputs.push((monitor, display_settings, output, config));
+        }
+
+        for (monitor, display_settings, output, config) in outputs {
+            // Mirrored outputs share their CRTC when they can, as `xrandr --same-as` does
+            if let Some((_, shared)) = target
+                .iter_mut()
+                .find(|(crtc, shared)| output.crtcs.contains(crtc) && shared.same_picture(&config))
+            {
+                shared.outputs.extend(config.outputs);
+                continue;
+            }
+
+            let crtc = if output.crtc != x11rb::NONE && !target.contains_key(&output.crtc) {
+                output.crtc
+            } else {
+                *output
+                    .crtcs
+                    .iter()
+                    .find(|crtc| !target.contains_key(crtc))
+                    .ok_or_else(|| ThisError::ChangeMonitorSettings {
+                        monitor: monitor.name.clone(),
+                        reason: String::from("no CRTC left"),
+                    })?
+            };
+
+            let rotations = self.get_crtc_info(crtc, resources.config_timestamp)?;
+            if !rotations.rotations.contains(config.rotation) {
+                Err(ThisError::ChangeMonitorSettings {
+                    monitor: monitor.name.clone(),
+                    reason: format!("can't be rotated to {}", display_settings.orientation),
+                })?;
+            }
+
+            target.insert(crtc, config);
+        }
+
+        let min_x = target.values().map(|c| c.x).min().unwrap_or_default();
+        let min_y = target.values().map(|c| c.y).min().unwrap_or_default();
+        for config in target.values_mut() {
+            config.x -= min_x;
+            config.y -= min_y;
+        }
+
+        Ok(Plan {
+            config_timestamp: resources.config_timestamp,
+            current,
+            target,
+            primary,
+        })
+    }
+}
+
+impl DisplayBackend for X11Backend {
+    fn enumerate(&self) -> Result<Vec<MonitorDevice>> {
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?;
+
+        let mut monitors = Vec::new();
+        for output in resources.outputs {
+            let info = self
+                .conn
+                .randr_get_output_info(output, resources.config_timestamp)?
+                .reply()?;
+
+            if info.connection == Connection::CONNECTED {
+                let name = String::from_utf8_lossy(&info.name).into_owned();
+                let edid = self
+                    .get_edid(output)?
+                    .and_then(|edid| Edid::parse(&edid).ok());
+                monitors.push(MonitorDevice {
+                    name: edid
+                        .as_ref()
+                        .and_then(Edid::stable_name)
+                        .unwrap_or(name.clone()),
+                    id: output.to_string(),
+                    legacy_name: Some(name),
+                    edid,
+                });
+            }
+        }
+
+        Ok(monitors)
+    }
+
+    fn get_display_settings(&self, monitor: &Monitor) -> Result<DisplaySettings> {
+        let output = self.get_output_info(monitor)?;
+        if output.crtc == x11rb::NONE {
+            return Ok(DisplaySettings::disabled());
+        }
+
+        let crtc = self.get_crtc_info(output.crtc, CURRENT_TIME)?;
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?;
+        let mode = resources.modes.iter().find(|mode| mode.id == crtc.mode);
+        let primary = self.conn.randr_get_output_primary(self.root)?.reply()?;
+
+        Ok(DisplaySettings {
+            position: Pos {
+                x: crtc.x as i32,
+                y: crtc.y as i32,
+            },
+            placement: None,
+            orientation: crtc.rotation.into(),
+            resolution: mode.map(|mode| Resolution {
+                width: mode.width as u32,
+                height: mode.height as u32,
+            }),
+            refresh_rate: mode.map(refresh_rate),
+            scale: None,
+            flipped: false,
+            primary: monitor.id == primary.output.to_string(),
+            enabled: true,
+            mirror: None,
+        })
+    }
+
+    fn get_modes(&self, monitor: &Monitor) -> Result<Vec<DisplayMode>> {
+        let output = self.get_output_info(monitor)?;
+        let resources = self
+            .conn
+            .randr_get_screen_resources_current(self.root)?
+            .reply()?;
+
+        Ok(resources
+            .modes
+            .iter()
+            .filter(|mode| output.modes.contains(&mode.id))
+            .map(|mode| DisplayMode {
+                resolution: Resolution {
+                    width: mode.width as u32,
+                    height: mode.height as u32,
+                },
+                refresh_rate: refresh_rate(mode),
+                bits_per_pixel: None,
+            })
+            .collect())
+    }
+
+    fn apply(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        let plan = self.plan(settings)?;
+
+        self.conn.grab_server()?;
+        let result = self.apply_plan(&plan);
+        self.conn.ungrab_server()?;
+        self.conn.flush()?;
+
+        result
+    }
+
+    /// RandR has no way to test a configuration, it is only checked against the outputs, CRTCs and screen size range
+    fn test(&self, settings: &[(&Monitor, &DisplaySettings)]) -> Result<()> {
+        let plan = self.plan(settings)?;
+        self.screen_size(&plan.target)?;
+        Ok(())
+    }
+
+    fn origin(&self) -> Origin {
+        Origin::TopLeft
+    }
+}
+
+/// Refresh rate of a mode in Hz, rounded to the closest integer
+fn refresh_rate(mode: &ModeInfo) -> u32 {
+    let mut vtotal = mode.vtotal as f64;
+    if mode.mode_flags.contains(ModeFlag::DOUBLE_SCAN) {
+        vtotal *= 2.;
+    }
+    if mode.mode_flags.contains(ModeFlag::INTERLACE) {
+        vtotal /= 2.;
+    }
+
+    if mode.htotal == 0 || vtotal == 0. {
+        0
+    } else {
+        (mode.dot_clock as f64 / (mode.htotal as f64 * vtotal)).round() as u32
+    }
+}
+
+/// Finds the mode of an output matching a resolution and, if any, a refresh rate
+fn find_mode<'a>(
+    modes: &'a [ModeInfo],
+    output_modes: &[Mode],
+    resolution: Resolution,
+    refresh: Option<u32>,
+) -> Option<&'a ModeInfo> {
+    modes
+        .iter()
+        .filter(|mode| output_modes.contains(&mode.id))
+        .filter(|mode| {
+            mode.width as u32 == resolution.width && mode.height as u32 == resolution.height
+        })
+        .filter(|mode| refresh.is_none_or(|refresh| refresh_rate(mode) == refresh))
+        .max_by_key(|mode| refresh_rate(mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::preset::Preset;
+    use std::{
+        io::{BufRead, BufReader},
+        process::{Child, Command, Stdio},
+        sync::Arc,
+        thread,
+        time::{Duration, Instant},
+    };
+
+    /// Xvfb screen of 1920x1080 with RandR, stopped once dropped
+    struct Xvfb {
+        process: Child,
+        display: String,
+    }
+
+    impl Xvfb {
+        fn start() -> Self {
+            let mut process = Command::new("Xvfb")
+                .args([
+                    "-displayfd",
+                    "1",
+                    "-screen",
+                    "0",
+                    "1920x1080x24",
+                    "+extension",
+                    "RANDR",
+                ])
+                .stdout(Stdio::piped())
+                .stderr(Stdio::null())
+                .spawn()
+                .expect("Xvfb must be installed to run this test");
+            let mut display = String::new();
+            BufReader::new(process.stdout.take().unwrap())
+                .read_line(&mut display)
+                .unwrap();
+            Self {
+                process,
+                display: format!(":{}", display.trim()),
+            }
+        }
+
+        fn backend(&self) -> X11Backend {
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                if let Ok((conn, screen_num)) = x11rb::connect(Some(&self.display)) {
+                    let root = conn.setup().roots[screen_num].root;
+                    return X11Backend {
+                        conn,
+                        screen_num,
+                        root,
+                    };
+                }
+                assert!(Instant::now() < deadline, "Xvfb didn't start");
+                thread::sleep(Duration::from_millis(100));
+            }
+        }
+    }
+
+    impl Drop for Xvfb {
+        fn drop(&mut self) {
+            let _ = self.process.kill();
+            let _ = self.process.wait();
+        }
+    }
+
+    #[test]
+    #[ignore = "needs Xvfb"]
+    fn enumerates_and_applies_a_mode_on_xvfb() {
+        let xvfb = Xvfb::start();
+        let backend = xvfb.backend();
+        let devices = backend.enumerate().unwrap();
+        assert_eq!(devices.len(), 1);
+
+        // Xvfb only has the mode of its screen, 1280x720@60 is added to its output
+        let name = b"1280x720";
+        let mode_info = ModeInfo {
+            id: 0,
+            width: 1280,
+            height: 720,
+            dot_clock: 74_250_000,
+            hsync_start: 1390,
+            hsync_end: 1430,
+            htotal: 1650,
+            hskew: 0,
+            vsync_start: 725,
+            vsync_end: 730,
+            vtotal: 750,
+            name_len: name.len() as u16,
+            mode_flags: ModeFlag::default(),
+        };
+        let mode = backend
+            .conn
+            .randr_create_mode(backend.root, mode_info, name)
+            .unwrap()
+            .reply()
+            .unwrap()
+            .mode;
+        let output: Output = devices[0].id.parse().unwrap();
+        backend
+            .conn
+            .randr_add_output_mode(output, mode)
+            .unwrap()
+            .check()
+            .unwrap();
+
+        let backend: Arc<dyn DisplayBackend> = Arc::new(backend);
+        let monitors = Monitor::get_monitors(&backend).unwrap();
+        let monitor = &monitors[0];
+        let current = monitor.get_display_settings().unwrap();
+        assert_eq!(
+            current.resolution,
+            Some(Resolution {
+                width: 1920,
+                height: 1080
+            })
+        );
+        assert!(monitor.get_modes().unwrap().iter().any(|mode| {
+            mode.resolution
+                == Resolution {
+                    width: 1280,
+                    height: 720,
+                }
+                && mode.refresh_rate == 60
+        }));
+
+        let target = DisplaySettings {
+            resolution: Some(Resolution {
+                width: 1280,
+                height: 720,
+            }),
+            refresh_rate: Some(60),
+            ..current
+        };
+        backend.test(&[(monitor, &target)]).unwrap();
+        backend.apply(&[(monitor, &target)]).unwrap();
+
+        let applied = monitor.get_display_settings().unwrap();
+        assert_eq!(applied.resolution, target.resolution);
+        assert_eq!(applied.refresh_rate, Some(60));
+        assert_eq!(applied.position, Pos { x: 0, y: 0 });
+        let preset = Preset::from_current_config(&monitors).unwrap();
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+}
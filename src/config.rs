@@ -0,0 +1,774 @@
+use crate::{error::ThisError, preset::Preset};
+use color_eyre::eyre::Result;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::{
+    env,
+    ffi::{OsStr, OsString},
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
+};
+use toml_edit::{DocumentMut, Item, Key, Table};
+
+/// Env var pointing to the presets file, or to the folder holding it
+pub const CONFIG_ENV: &str = "WIMLAM_CONFIG";
+/// File that, next to the executable, makes the app keep its presets there
+pub const PORTABLE_MARKER: &str = "portable";
+const FILE_NAME: &str = "monitors_config.json";
+
+/// Version of the presets file written by this build, bumped along with a new migration whenever the format changes
+pub const VERSION: u32 = 1;
+
+/// Number of backups kept of the previous contents of the file, see [`write`]
+pub const BACKUPS: usize = 5;
+
+/// Turns a file of version `i` into one of version `i + 1`
+const MIGRATIONS: [fn(Value) -> Result<Value>; VERSION as usize] = [from_bare_array];
+
+/// Content of the presets file
+#[derive(Serialize, Deserialize)]
+struct Document<P> {
+    version: u32,
+    presets: P,
+}
+
+/// Format of a presets file, told by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Json,
+    /// Meant to be edited by hand, comments are kept when the app rewrites the file
+    Toml,
+}
+
+impl Format {
+    /// TOML for a `.toml` file, JSON otherwise
+    pub fn of(path: &Path) -> Self {
+        match path.extension().and_then(OsStr::to_str) {
+            Some(extension) if extension.eq_ignore_ascii_case("toml") => Self::Toml,
+            _ => Self::Json,
+        }
+    }
+
+    fn extension(self) -> &'static str {
+        match self {
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    fn parse(self, s: &str) -> Result<Value> {
+        Ok(match self {
+            Self::Json => serde_json::from_str(s)?,
+            Self::Toml => toml_edit::de::from_str(s)?,
+        })
+    }
+
+    /// Serialises a document, a TOML one keeping the comments of the previous content of the file
+    fn serialize<P: Serialize>(
+        self,
+        document: &Document<P>,
+        previous: Option<&str>,
+    ) -> Result<String> {
+        Ok(match self {
+            Self::Json => serde_json::to_string(document)?,
+            Self::Toml => {
+                let mut toml = toml_edit::ser::to_document(document)?;
+                expand(toml.as_table_mut(), TOML_TABLE_DEPTH);
+                if let Some(previous) = previous.and_then(|s| s.parse::<DocumentMut>().ok()) {
+                    keep_comments(previous.as_table(), toml.as_table_mut());
+                }
+                toml.to_string()
+            }
+        })
+    }
+}
+
+/// Where the presets are kept: the `--config` argument, the `WIMLAM_CONFIG` env var,
+/// next to the executable in portable mode, or else in the config folder of the platform
+///
+/// A folder stands for the presets file inside it
+pub fn path(argument: Option<&str>) -> Result<PathBuf> {
+    resolve(
+        argument.map(PathBuf::from),
+        env::var_os(CONFIG_ENV),
+        env::current_exe()
+            .ok()
+            .and_then(|exe| portable_folder(&exe)),
+        default_path,
+    )
+}
+
+/// [`path`] from the argument, the env var, the portable folder and the default path
+fn resolve(
+    argument: Option<PathBuf>,
+    env: Option<OsString>,
+    portable_folder: Option<PathBuf>,
+    default_path: impl FnOnce() -> Result<PathBuf>,
+) -> Result<PathBuf> {
+    let path = match argument.or_else(|| env.filter(|path| !path.is_empty()).map(PathBuf::from)) {
+        Some(path) => path,
+        None => match portable_folder {
+            Some(folder) => folder,
+            None => default_path()?,
+        },
+    };
+
+    Ok(if path.is_dir() {
+        path.join(FILE_NAME)
+    } else {
+        path
+    })
+}
+
+/// Folder of the executable when the portable marker is next to it
+fn portable_folder(exe: &Path) -> Option<PathBuf> {
+    let folder = exe.parent()?;
+    folder
+        .join(PORTABLE_MARKER)
+        .exists()
+        .then(|| folder.to_path_buf())
+}
+
+#[cfg(windows)]
+fn default_path() -> Result<PathBuf> {
+    Ok(dirs::config_dir()
+        .ok_or(ThisError::NoConfigDir)?
+        .join("Adrien5902")
+        .join(FILE_NAME))
+}
+
+#[cfg(not(windows))]
+fn default_path() -> Result<PathBuf> {
+    default_path_in(&dirs::config_dir().ok_or(ThisError::NoConfigDir)?)
+}
+
+/// `$XDG_CONFIG_HOME/wimlam`, `~/.config/wimlam` by default,
+/// the folder of older versions is kept as long as it holds the presets
+#[cfg(not(windows))]
+fn default_path_in(config_dir: &Path) -> Result<PathBuf> {
+    let path = config_dir.join("wimlam").join(FILE_NAME);
+    let legacy_path = config_dir.join("Adrien5902").join(FILE_NAME);
+
+    Ok(if !fs::exists(&path)? && fs::exists(&legacy_path)? {
+        legacy_path
+    } else {
+        path
+    })
+}
+
+/// Reads the presets of a file, migrating it to the current version first
+///
+/// The file as it was before migrating is kept next to it, see [`migration_backup_path`]
+pub fn read(path: &Path) -> Result<Vec<Preset>> {
+    if !fs::exists(path)? {
+        return Ok(Vec::new());
+    }
+
+    let s = fs::read_to_string(path)?;
+    let (presets, version) = parse(&s, Format::of(path))?;
+    if version < VERSION {
+        let backup = migration_backup_path(path, version);
+        fs::rename(write_temporary(&backup, Format::of(path), &s)?, backup)?;
+        write(path, &presets)?;
+    }
+    Ok(presets)
+}
+
+/// Presets of the content of a file, migrated to the current version, along with the version it had
+fn parse(s: &str, format: Format) -> Result<(Vec<Preset>, u32)> {
+    let mut value = format.parse(s)?;
+
+    let version = version(&value)?;
+    for migration in &MIGRATIONS[version as usize..] {
+        value = migration(value)?;
+    }
+
+    let document: Document<Vec<Preset>> = serde_json::from_value(value)?;
+    Ok((document.presets, version))
+}
+
+/// Writes the presets to a temporary file then moves it over the file, whose previous content is backed up
+///
+/// A crash or a full disk while writing leaves the temporary file broken, never the file itself
+pub fn write<P: Serialize>(path: &Path, presets: P) -> Result<()> {
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let format = Format::of(path);
+    let previous = fs::read_to_string(path).ok();
+    let s = format.serialize(
+        &Document {
+            version: VERSION,
+            presets,
+        },
+        previous.as_deref(),
+    )?;
+    let temporary = write_temporary(path, format, &s)?;
+
+    if fs::exists(path)? {
+        backup(path)?;
+    }
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Writes the content next to the file, to be moved over it once entirely on disk
+fn write_temporary(path: &Path, format: Format, s: &str) -> Result<PathBuf> {
+    let temporary = path.with_extension(format!("{}.tmp", format.extension()));
+    let mut file = File::create(&temporary)?;
+    file.write_all(s.as_bytes())?;
+    file.sync_all()?;
+    Ok(temporary)
+}
+
+/// Copies the file to its backups folder, named after the current time, and removes the backups past the [`BACKUPS`] newest
+///
+/// Saves within the same millisecond are named after the following ones, so they neither overwrite each other nor lose their order
+fn backup(path: &Path) -> Result<()> {
+    let folder = backups_folder(path);
+    fs::create_dir_all(&folder)?;
+
+    let mut timestamp = SystemTime::now().duration_since(UNIX_EPOCH)?.as_millis();
+    let backup = loop {
+        let backup = folder.join(format!(
+            "{}.{}.{}",
+            stem(path),
+            timestamp,
+            Format::of(path).extension()
+        ));
+        if !fs::exists(&backup)? {
+            break backup;
+        }
+        timestamp += 1;
+    };
+    fs::copy(path, backup)?;
+
+    for backup in backups(path)?.into_iter().skip(BACKUPS) {
+        fs::remove_file(backup)?;
+    }
+    Ok(())
+}
+
+fn backups_folder(path: &Path) -> PathBuf {
+    path.with_file_name("backups")
+}
+
+fn stem(path: &Path) -> String {
+    path.file_stem()
+        .unwrap_or_default()
+        .to_string_lossy()
+        .to_string()
+}
+
+/// Backups of the file, newest first
+fn backups(path: &Path) -> Result<Vec<PathBuf>> {
+    let folder = backups_folder(path);
+    if !fs::exists(&folder)? {
+        return Ok(Vec::new());
+    }
+
+    let prefix = format!("{}.", stem(path));
+    let suffix = format!(".{}", Format::of(path).extension());
+    let mut backups: Vec<(u128, PathBuf)> = fs::read_dir(folder)?
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            let timestamp = path
+                .file_name()?
+                .to_str()?
+                .strip_prefix(&prefix)?
+                .strip_suffix(&suffix)?
+                .parse()
+                .ok()?;
+            Some((timestamp, path))
+        })
+        .collect();
+    backups.sort_by(|(a, _), (b, _)| b.cmp(a));
+    Ok(backups.into_iter().map(|(_, path)| path).collect())
+}
+
+/// Newest backup of the file that can still be read
+pub fn newest_valid_backup(path: &Path) -> Result<Option<PathBuf>> {
+    Ok(backups(path)?.into_iter().find(|backup| {
+        fs::read_to_string(backup).is_ok_and(|s| parse(&s, Format::of(path)).is_ok())
+    }))
+}
+
+/// Puts a backup in place of the file, which is kept next to it as `monitors_config.broken.json`...
+pub fn restore(path: &Path, backup: &Path) -> Result<Vec<Preset>> {
+    if fs::exists(path)? {
+        fs::rename(
+            path,
+            path.with_file_name(format!(
+                "{}.broken.{}",
+                stem(path),
+                Format::of(path).extension()
+            )),
+        )?;
+    }
+    fs::copy(backup, path)?;
+    read(path)
+}
+
+/// Where the file is copied before being migrated from the given version, `monitors_config.v0.bak.json`...
+pub fn migration_backup_path(path: &Path, version: u32) -> PathBuf {
+    path.with_file_name(format!(
+        "{}.v{}.bak.{}",
+        stem(path),
+        version,
+        Format::of(path).extension()
+    ))
+}
+
+/// Writes presets to a standalone file, in the format of its extension, to be imported with [`import`]
+///
+/// An existing file is only replaced when `overwrite` is set, in one go like [`write`] does
+pub fn export(path: &Path, presets: &[Preset], overwrite: bool) -> Result<()> {
+    if !overwrite && fs::exists(path)? {
+        Err(ThisError::FileExists(path.to_path_buf()))?;
+    }
+
+    let format = Format::of(path);
+    let s = format.serialize(
+        &Document {
+            version: VERSION,
+            presets,
+        },
+        None,
+    )?;
+    let temporary = write_temporary(path, format, &s)?;
+    fs::rename(&temporary, path)?;
+    Ok(())
+}
+
+/// Presets of a file written by [`export`], or of another presets file, which is left untouched
+pub fn import(path: &Path) -> Result<Vec<Preset>> {
+    if !fs::exists(path)? {
+        Err(ThisError::ConfigNotFound(path.to_path_buf()))?;
+    }
+    let (presets, _) = parse(&fs::read_to_string(path)?, Format::of(path))?;
+    Ok(presets)
+}
+
+/// Writes the presets of a file to another one, in the format of its extension, the file being left untouched
+pub fn convert(from: &Path, to: &Path) -> Result<()> {
+    if !fs::exists(from)? {
+        Err(ThisError::ConfigNotFound(from.to_path_buf()))?;
+    }
+    // Not `read`, which would migrate the file in place
+    let (presets, _) = parse(&fs::read_to_string(from)?, Format::of(from))?;
+    write(to, presets)
+}
+
+/// Levels of nesting written as `[tables]`, deeper ones are inline: presets, their map, and its settings
+const TOML_TABLE_DEPTH: usize = 3;
+
+/// Turns the inline tables and arrays of inline tables the serializer writes into `[tables]`, down to `depth` levels
+fn expand(table: &mut Table, depth: usize) {
+    if depth == 0 {
+        return;
+    }
+
+    for (_, item) in table.iter_mut() {
+        let expanded = match std::mem::take(item) {
+            Item::Value(value) if value.is_inline_table() => {
+                Item::Value(value).into_table().map(Item::Table)
+            }
+            // An empty array of tables isn't written at all
+            Item::Value(value) if value.as_array().is_some_and(|array| !array.is_empty()) => {
+                Item::Value(value)
+                    .into_array_of_tables()
+                    .map(Item::ArrayOfTables)
+            }
+            item => Err(item),
+        };
+        *item = expanded.unwrap_or_else(|item| item);
+
+        match item {
+            Item::Table(table) => {
+                expand(table, depth - 1);
+                // No `[presets.map]` header above `[presets.map.<monitor>]`, which are sorted rather than in hash order
+                if !table.is_empty() && table.iter().all(|(_, item)| item.is_table()) {
+                    table.set_implicit(true);
+                    table.sort_values();
+                }
+            }
+            Item::ArrayOfTables(tables) => {
+                for table in tables.iter_mut() {
+                    expand(table, depth - 1);
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Gives the tables and keys of a TOML document the comments and order they had in its previous version,
+/// presets being matched by name, or else by index when renamed
+fn keep_comments(previous: &Table, table: &mut Table) {
+    *table.decor_mut() = previous.decor().clone();
+    table.sort_values_by(|a, _, b, _| {
+        let index = |key: &Key| {
+            previous
+                .iter()
+                .position(|(other, _)| other == key.get())
+                .unwrap_or(usize::MAX)
+        };
+        index(a).cmp(&index(b))
+    });
+
+    for (mut key, item) in table.iter_mut() {
+        let Some((previous_key, previous_item)) = previous.get_key_value(key.get()) else {
+            continue;
+        };
+        *key.leaf_decor_mut() = previous_key.leaf_decor().clone();
+
+        match (previous_item, item) {
+            (Item::Table(previous), Item::Table(table)) => keep_comments(previous, table),
+            (Item::ArrayOfTables(previous), Item::ArrayOfTables(tables)) => {
+                for (index, table) in tables.iter_mut().enumerate() {
+                    let name = table.get("name").and_then(Item::as_str).map(String::from);
+                    if let Some(previous) = previous
+                        .iter()
+                        .find(|previous| {
+                            previous.get("name").and_then(Item::as_str) == name.as_deref()
+                        })
+                        .or_else(|| previous.get(index))
+                    {
+                        keep_comments(previous, table);
+                    }
+                }
+            }
+            (Item::Value(previous), Item::Value(value)) => {
+                // Comments at the end of the line
+                if let Some(suffix) = previous.decor().suffix() {
+                    value.decor_mut().set_suffix(suffix.clone());
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Files written before the format was versioned are a bare array of presets
+fn version(value: &Value) -> Result<u32> {
+    let version = match value {
+        Value::Array(_) => 0,
+        Value::Object(document) => document
+            .get("version")
+            .and_then(Value::as_u64)
+            .ok_or(ThisError::InvalidConfig("missing version"))?
+            as u32,
+        _ => Err(ThisError::InvalidConfig(
+            "neither a list of presets nor a document",
+        ))?,
+    };
+
+    if version > VERSION {
+        Err(ThisError::ConfigTooNew(version))?;
+    }
+    Ok(version)
+}
+
+/// Wraps the presets in a versioned document, and names the presets saved before they had a name
+fn from_bare_array(value: Value) -> Result<Value> {
+    let Value::Array(mut presets) = value else {
+        Err(ThisError::InvalidConfig("version 0 is a list of presets"))?
+    };
+
+    let mut names: Vec<String> = presets
+        .iter()
+        .filter_map(|preset| preset.get("name")?.as_str())
+        .map(String::from)
+        .collect();
+    for preset in &mut presets {
+        let Value::Object(preset) = preset else {
+            Err(ThisError::InvalidConfig("a preset isn't an object"))?
+        };
+        if preset
+            .get("name")
+            .and_then(Value::as_str)
+            .is_none_or(str::is_empty)
+        {
+            let name = Preset::default_name(names.iter().map(String::as_str));
+            preset.insert(String::from("name"), Value::String(name.clone()));
+            names.push(name);
+        }
+    }
+
+    let mut document = Map::new();
+    document.insert(String::from("version"), Value::from(1));
+    document.insert(String::from("presets"), Value::Array(presets));
+    Ok(Value::Object(document))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn preset(name: &str) -> Preset {
+        let mut preset = Preset::default();
+        preset.name = String::from(name);
+        preset
+    }
+
+    fn names(presets: &[Preset]) -> Vec<&str> {
+        presets.iter().map(|preset| preset.name.as_str()).collect()
+    }
+
+    #[test]
+    fn writes_through_a_temporary_file() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join(FILE_NAME);
+
+        write(&path, [preset("Desk")]).unwrap();
+        write(&path, [preset("Sofa")]).unwrap();
+
+        assert_eq!(names(&read(&path).unwrap()), ["Sofa"]);
+        assert!(!fs::exists(path.with_extension("json.tmp")).unwrap());
+        let backups = backups(&path).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(names(&import(&backups[0]).unwrap()), ["Desk"]);
+    }
+
+    #[test]
+    fn keeps_the_newest_backups() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join(FILE_NAME);
+
+        // Written within the same millisecond most of the time
+        for index in 0..BACKUPS + 3 {
+            write(&path, [preset(&format!("Preset {}", index))]).unwrap();
+        }
+
+        let backups = backups(&path).unwrap();
+        assert_eq!(backups.len(), BACKUPS);
+        let backed_up: Vec<String> = backups
+            .iter()
+            .map(|backup| import(backup).unwrap().remove(0).name)
+            .collect();
+        let expected: Vec<String> = (2..BACKUPS + 2)
+            .rev()
+            .map(|index| format!("Preset {}", index))
+            .collect();
+        assert_eq!(backed_up, expected);
+    }
+
+    #[test]
+    fn restores_the_newest_valid_backup() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join(FILE_NAME);
+        for name in ["Desk", "Sofa", "Travel"] {
+            write(&path, [preset(name)]).unwrap();
+        }
+        let backups = backups(&path).unwrap();
+        fs::write(&backups[0], "{ \"version\": 1, \"presets\": [").unwrap();
+        fs::write(&path, "not even json").unwrap();
+
+        let backup = newest_valid_backup(&path).unwrap().unwrap();
+        assert_eq!(backup, backups[1]);
+        assert_eq!(names(&restore(&path, &backup).unwrap()), ["Desk"]);
+        assert_eq!(names(&read(&path).unwrap()), ["Desk"]);
+        assert_eq!(
+            fs::read_to_string(folder.path().join("monitors_config.broken.json")).unwrap(),
+            "not even json"
+        );
+    }
+
+    #[test]
+    fn names_the_presets_of_a_bare_array() {
+        let value = serde_json::json!([
+            { "name": "Preset 1", "keybind": null, "map": {} },
+            { "keybind": null, "map": {} },
+            { "name": "", "keybind": null, "map": {} },
+        ]);
+
+        let document = from_bare_array(value).unwrap();
+        assert_eq!(document["version"], 1);
+        let names: Vec<&str> = document["presets"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|preset| preset["name"].as_str().unwrap())
+            .collect();
+        assert_eq!(names, ["Preset 1", "Preset 2", "Preset 3"]);
+        assert!(from_bare_array(serde_json::json!({ "presets": [] })).is_err());
+    }
+
+    #[test]
+    fn migrates_old_files_to_the_current_version() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join(FILE_NAME);
+        let old = r#"[{ "keybind": null, "map": {} }]"#;
+        fs::write(&path, old).unwrap();
+
+        assert_eq!(names(&read(&path).unwrap()), ["Preset 1"]);
+        let (presets, version) = parse(&fs::read_to_string(&path).unwrap(), Format::Json).unwrap();
+        assert_eq!(version, VERSION);
+        assert_eq!(names(&presets), ["Preset 1"]);
+        assert_eq!(
+            fs::read_to_string(migration_backup_path(&path, 0)).unwrap(),
+            old
+        );
+        assert!(!fs::exists(migration_backup_path(&path, 0).with_extension("json.tmp")).unwrap());
+    }
+
+    #[test]
+    fn refuses_files_of_a_newer_version() {
+        let s = format!(r#"{{ "version": {}, "presets": [] }}"#, VERSION + 1);
+        let err = parse(&s, Format::Json).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ThisError>(),
+            Some(ThisError::ConfigTooNew(version)) if *version == VERSION + 1
+        ));
+        assert!(parse(r#"{ "presets": [] }"#, Format::Json).is_err());
+    }
+
+    #[test]
+    fn converts_without_migrating_the_source() {
+        let folder = TempDir::new().unwrap();
+        let from = folder.path().join(FILE_NAME);
+        let to = folder.path().join("monitors_config.toml");
+        let old = r#"[{ "name": "Desk", "keybind": null, "map": {} }]"#;
+        fs::write(&from, old).unwrap();
+
+        convert(&from, &to).unwrap();
+        assert_eq!(fs::read_to_string(&from).unwrap(), old);
+        assert!(!fs::exists(migration_backup_path(&from, 0)).unwrap());
+        assert_eq!(names(&read(&to).unwrap()), ["Desk"]);
+    }
+
+    #[test]
+    fn keeps_the_comments_of_toml_files() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join("monitors_config.toml");
+        fs::write(
+            &path,
+            r#"# Presets of the desk
+version = 1
+
+# Docked
+[[presets]]
+name = "Desk"
+
+# The screen of the laptop
+[presets.map.LAPTOP]
+orientation = "Landscape" # lid open
+position = { x = 0, y = 0 }
+resolution = { width = 1920, height = 1080 }
+
+[[presets]]
+name = "Sofa" # TV only
+
+[presets.map.TV]
+orientation = "Landscape"
+position = { x = 0, y = 0 }
+"#,
+        )
+        .unwrap();
+
+        let presets = read(&path).unwrap();
+        write(&path, &presets).unwrap();
+
+        let s = fs::read_to_string(&path).unwrap();
+        for comment in [
+            "# Presets of the desk",
+            "# Docked",
+            "# The screen of the laptop",
+            "# lid open",
+            "# TV only",
+        ] {
+            assert!(s.contains(comment), "{} missing from\n{}", comment, s);
+        }
+        let written = read(&path).unwrap();
+        assert_eq!(names(&written), ["Desk", "Sofa"]);
+        assert_eq!(
+            written.iter().map(Preset::layout).collect::<Vec<_>>(),
+            presets.iter().map(Preset::layout).collect::<Vec<_>>()
+        );
+        assert!(written[0].layout().contains("LAPTOP"));
+    }
+
+    #[test]
+    fn exports_and_imports_presets() {
+        let folder = TempDir::new().unwrap();
+        let path = folder.path().join("export.toml");
+
+        export(&path, &[preset("Desk"), preset("Sofa")], false).unwrap();
+        assert_eq!(names(&import(&path).unwrap()), ["Desk", "Sofa"]);
+
+        let err = export(&path, &[preset("Travel")], false).unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<ThisError>(),
+            Some(ThisError::FileExists(existing)) if *existing == path
+        ));
+        assert_eq!(names(&import(&path).unwrap()), ["Desk", "Sofa"]);
+
+        export(&path, &[preset("Travel")], true).unwrap();
+        assert_eq!(names(&import(&path).unwrap()), ["Travel"]);
+        assert!(!fs::exists(path.with_extension("toml.tmp")).unwrap());
+        assert!(!fs::exists(backups_folder(&path)).unwrap());
+    }
+
+    #[test]
+    fn resolves_the_path_in_order() {
+        let folder = TempDir::new().unwrap();
+        let argument = folder.path().join("argument.json");
+        let env = folder.path().join("env.json");
+        let portable = folder.path().join("portable");
+        fs::create_dir(&portable).unwrap();
+        let default = || Ok(folder.path().join("default.json"));
+        let resolve = |argument: Option<&Path>, env: Option<&Path>, portable: Option<&Path>| {
+            resolve(
+                argument.map(Path::to_path_buf),
+                env.map(OsString::from),
+                portable.map(Path::to_path_buf),
+                default,
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            resolve(Some(&argument), Some(&env), Some(&portable)),
+            argument
+        );
+        assert_eq!(resolve(None, Some(&env), Some(&portable)), env);
+        assert_eq!(
+            resolve(None, Some(Path::new("")), Some(&portable)),
+            portable.join(FILE_NAME)
+        );
+        assert_eq!(resolve(None, None, None), default().unwrap());
+        // A folder stands for the file inside it
+        assert_eq!(
+            resolve(Some(folder.path()), None, None),
+            folder.path().join(FILE_NAME)
+        );
+    }
+
+    #[test]
+    fn finds_the_portable_marker_next_to_the_executable() {
+        let folder = TempDir::new().unwrap();
+        let exe = folder.path().join("WiMLaM");
+
+        assert_eq!(portable_folder(&exe), None);
+        fs::write(folder.path().join(PORTABLE_MARKER), "").unwrap();
+        assert_eq!(portable_folder(&exe), Some(folder.path().to_path_buf()));
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn keeps_the_legacy_folder_while_it_holds_the_presets() {
+        let config_dir = TempDir::new().unwrap();
+        let path = config_dir.path().join("wimlam").join(FILE_NAME);
+        let legacy_path = config_dir.path().join("Adrien5902").join(FILE_NAME);
+
+        assert_eq!(default_path_in(config_dir.path()).unwrap(), path);
+        write(&legacy_path, [preset("Desk")]).unwrap();
+        assert_eq!(default_path_in(config_dir.path()).unwrap(), legacy_path);
+        write(&path, [preset("Desk")]).unwrap();
+        assert_eq!(default_path_in(config_dir.path()).unwrap(), path);
+    }
+}
@@ -0,0 +1,169 @@
+use color_eyre::eyre::Result;
+use std::{
+    io::{self, IsTerminal, Write},
+    time::Duration,
+};
+
+pub use self::dialog::{check, keep_settings};
+
+/// Time left to confirm new display settings before the previous ones are restored
+pub const TIMEOUT: Duration = Duration::from_secs(15);
+const TITLE: &str = "WiMLaM";
+
+pub fn message(remaining: Duration) -> String {
+    format!(
+        "Keep these display settings?\nReverting to the previous ones in {} seconds",
+        remaining.as_secs()
+    )
+}
+
+/// Asks a yes or no question, in the terminal when there is one and through a dialog otherwise
+pub fn ask(question: &str) -> Result<bool> {
+    if !io::stdin().is_terminal() {
+        return dialog::ask(question);
+    }
+
+    print!("{} [y/N] ", question);
+    io::stdout().flush()?;
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer)?;
+    Ok(matches!(answer.trim(), "y" | "Y" | "yes"))
+}
+
+#[cfg(windows)]
+mod dialog {
+    use super::{TIMEOUT, TITLE, message};
+    use color_eyre::eyre::Result;
+    use std::{
+        sync::mpsc::{self, RecvTimeoutError},
+        thread,
+        time::{Duration, Instant},
+    };
+    use windows::{
+        Win32::{
+            Foundation::{LPARAM, WPARAM},
+            UI::WindowsAndMessaging::{
+                FindWindowW, IDOK, IDYES, MB_ICONQUESTION, MB_OKCANCEL, MB_SETFOREGROUND,
+                MB_TOPMOST, MB_YESNO, MessageBoxW, PostMessageW, SetDlgItemTextW, WM_CLOSE,
+            },
+        },
+        core::{HSTRING, w},
+    };
+
+    /// Id of the text of a message box
+    const TEXT_ID: i32 = 0xFFFF;
+
+    /// Message boxes are always available
+    pub fn check() -> Result<()> {
+        Ok(())
+    }
+
+    pub fn ask(question: &str) -> Result<bool> {
+        let result = unsafe {
+            MessageBoxW(
+                None,
+                &HSTRING::from(question),
+                &HSTRING::from(TITLE),
+                MB_YESNO | MB_ICONQUESTION | MB_TOPMOST | MB_SETFOREGROUND,
+            )
+        };
+        Ok(result == IDYES)
+    }
+
+    /// Asks through a message box counting down, which is closed once the time is up
+    pub fn keep_settings() -> Result<bool> {
+        let (sender, receiver) = mpsc::channel();
+        thread::spawn(move || {
+            let result = unsafe {
+                MessageBoxW(
+                    None,
+                    &HSTRING::from(message(TIMEOUT)),
+                    &HSTRING::from(TITLE),
+                    MB_OKCANCEL | MB_ICONQUESTION | MB_TOPMOST | MB_SETFOREGROUND,
+                )
+            };
+            let _ = sender.send(result == IDOK);
+        });
+
+        let deadline = Instant::now() + TIMEOUT;
+        loop {
+            match receiver.recv_timeout(Duration::from_secs(1)) {
+                Ok(keep) => return Ok(keep),
+                Err(RecvTimeoutError::Disconnected) => return Ok(false),
+                Err(RecvTimeoutError::Timeout) => {}
+            }
+
+            let Ok(window) = (unsafe { FindWindowW(w!("#32770"), &HSTRING::from(TITLE)) }) else {
+                continue;
+            };
+
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                // Closing the message box answers Cancel
+                unsafe { PostMessageW(Some(window), WM_CLOSE, WPARAM(0), LPARAM(0))? };
+                return Ok(receiver.recv().unwrap_or(false));
+            }
+
+            unsafe { SetDlgItemTextW(window, TEXT_ID, &HSTRING::from(message(remaining)))? };
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod dialog {
+    use super::{TIMEOUT, TITLE, message};
+    use crate::error::ThisError;
+    use color_eyre::eyre::Result;
+    use std::{
+        io::{self, ErrorKind},
+        process::{Command, ExitStatus, Stdio},
+    };
+
+    /// Fails when zenity, which asks every question outside of a terminal, isn't installed
+    pub fn check() -> Result<()> {
+        zenity(
+            Command::new("zenity")
+                .arg("--version")
+                .stdout(Stdio::null())
+                .status(),
+        )?;
+        Ok(())
+    }
+
+    /// Whether zenity answered yes
+    fn zenity(status: io::Result<ExitStatus>) -> Result<bool> {
+        match status {
+            Ok(status) => Ok(status.success()),
+            Err(err) if err.kind() == ErrorKind::NotFound => Err(ThisError::ZenityMissing)?,
+            Err(err) => Err(err)?,
+        }
+    }
+
+    pub fn ask(question: &str) -> Result<bool> {
+        zenity(
+            Command::new("zenity")
+                .args(["--question", "--title", TITLE, "--text", question])
+                .status(),
+        )
+    }
+
+    /// Asks through zenity, which answers no once the time is up
+    pub fn keep_settings() -> Result<bool> {
+        let status = Command::new("zenity")
+            .args([
+                "--question",
+                "--title",
+                TITLE,
+                "--text",
+                &message(TIMEOUT),
+                "--ok-label",
+                "Keep",
+                "--cancel-label",
+                "Revert",
+                "--timeout",
+                &TIMEOUT.as_secs().to_string(),
+            ])
+            .status();
+        zenity(status)
+    }
+}
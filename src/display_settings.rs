@@ -1,6 +1,8 @@
+use crate::monitor::MonitorName;
 use num_enum::TryFromPrimitive;
 use serde::{Deserialize, Serialize};
 use std::fmt::Display;
+#[cfg(windows)]
 use windows::Win32::{
     Foundation::POINTL,
     Graphics::Gdi::{DEVMODE_DISPLAY_ORIENTATION, DEVMODEW},
@@ -8,20 +10,205 @@ use windows::Win32::{
 
 #[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct DisplaySettings {
+    /// Ignored when the monitor is placed next to another one
+    #[serde(default)]
     pub position: Pos,
+    /// Where the monitor sits relatively to another one, solved into its position when applied
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<Placement>,
     pub orientation: DisplayOrientation,
+    /// Keeps the monitor's current resolution when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub resolution: Option<Resolution>,
+    /// Refresh rate in Hz, keeps the monitor's current one when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub refresh_rate: Option<u32>,
+    /// How much bigger the picture is shown, the monitor then covers its resolution divided by the scale of the desktop.
+    /// Keeps the monitor's current one when missing, only Wayland compositors scale monitors
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub scale: Option<f64>,
+    /// Shows the picture mirrored left to right, only Wayland compositors flip monitors
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub flipped: bool,
+    /// Holds the taskbar / panel, the primary monitor always sits at 0,0
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub primary: bool,
+    /// Turned off monitors ignore every other setting
+    #[serde(default = "enabled", skip_serializing_if = "is_enabled")]
+    pub enabled: bool,
+    /// Monitor this one shows the same picture as, its position, orientation and resolution are then shared
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mirror: Option<MonitorName>,
+}
+
+fn enabled() -> bool {
+    true
+}
+
+fn is_enabled(enabled: &bool) -> bool {
+    *enabled
+}
+
+impl DisplaySettings {
+    /// Settings of a monitor that is connected but turned off
+    pub fn disabled() -> Self {
+        Self {
+            position: Pos { x: 0, y: 0 },
+            placement: None,
+            orientation: DisplayOrientation::Landscape,
+            resolution: None,
+            refresh_rate: None,
+            scale: None,
+            flipped: false,
+            primary: false,
+            enabled: false,
+            mirror: None,
+        }
+    }
+
+    /// Whether applying these settings would change the current ones of a monitor,
+    /// missing values and monitors not made primary don't count as changes
+    pub fn differs_from(&self, current: &Self) -> bool {
+        if !self.enabled || !current.enabled {
+            return self.enabled != current.enabled;
+        }
+
+        self.position != current.position
+            || self.orientation != current.orientation
+            || self
+                .resolution
+                .is_some_and(|r| Some(r) != current.resolution)
+            || self
+                .refresh_rate
+                .is_some_and(|r| Some(r) != current.refresh_rate)
+            || self.scale.is_some_and(|s| Some(s) != current.scale)
+            || self.flipped != current.flipped
+            || self.primary && !current.primary
+    }
+
+    /// Whether both monitors are on and show the same area of the desktop
+    pub fn covers_same_area(&self, other: &Self) -> bool {
+        self.enabled
+            && other.enabled
+            && self.position == other.position
+            && self
+                .resolution
+                .map(|r| r.oriented(self.orientation).scaled(self.scale))
+                == other
+                    .resolution
+                    .map(|r| r.oriented(other.orientation).scaled(other.scale))
+    }
 }
 
 impl Display for DisplaySettings {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(&format!(
-            "{}/{},{}",
-            self.position.x, self.position.y, self.orientation
-        ))
+        if !self.enabled {
+            return f.write_str("off");
+        }
+
+        match &self.placement {
+            Some(placement) => f.write_str(&format!("{},{}", placement, self.orientation))?,
+            None => f.write_str(&format!(
+                "{}/{},{}",
+                self.position.x, self.position.y, self.orientation
+            ))?,
+        }
+        if self.flipped {
+            f.write_str(",flipped")?;
+        }
+        if let Some(resolution) = self.resolution {
+            f.write_str(&format!(",{}", resolution))?;
+        }
+        if let Some(refresh_rate) = self.refresh_rate {
+            f.write_str(&format!("@{}", refresh_rate))?;
+        }
+        if let Some(scale) = self.scale {
+            f.write_str(&format!(",scale {}", scale))?;
+        }
+        if self.primary {
+            f.write_str(",primary")?;
+        }
+        if let Some(mirror) = &self.mirror {
+            f.write_str(&format!(",mirrors {}", mirror))?;
+        }
+        Ok(())
+    }
+}
+
+/// Size of a display mode in pixels, as seen in landscape whatever the orientation of the monitor
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Display for Resolution {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}x{}", self.width, self.height))
+    }
+}
+
+/// A mode supported by a monitor
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+pub struct DisplayMode {
+    pub resolution: Resolution,
+    /// Refresh rate in Hz
+    pub refresh_rate: u32,
+    /// Color depth, when the backend reports it
+    pub bits_per_pixel: Option<u32>,
+}
+
+impl Display for DisplayMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&format!("{}@{}", self.resolution, self.refresh_rate))?;
+        if let Some(bits_per_pixel) = self.bits_per_pixel {
+            f.write_str(&format!(" ({} bits)", bits_per_pixel))?;
+        }
+        Ok(())
     }
 }
 
-#[derive(Debug, Serialize, Deserialize, Clone, Copy, TryFromPrimitive)]
+impl DisplayMode {
+    /// Whether the mode can display the resolution and refresh rate requested by the settings,
+    /// missing values being taken from the monitor's current settings
+    pub fn matches(&self, settings: &DisplaySettings, current: &DisplaySettings) -> bool {
+        settings
+            .resolution
+            .or(current.resolution)
+            .is_none_or(|resolution| resolution == self.resolution)
+            && settings
+                .refresh_rate
+                .is_none_or(|refresh_rate| refresh_rate == self.refresh_rate)
+    }
+}
+
+impl Resolution {
+    /// Size of the area covered by the monitor once rotated
+    pub fn oriented(&self, orientation: DisplayOrientation) -> Self {
+        if orientation.is_same_direction(&DisplayOrientation::Landscape) {
+            *self
+        } else {
+            Self {
+                width: self.height,
+                height: self.width,
+            }
+        }
+    }
+
+    /// Size of the area of the desktop covered by the monitor once scaled, in the pixels of the desktop
+    pub fn scaled(&self, scale: Option<f64>) -> Self {
+        match scale.filter(|scale| *scale > 0.) {
+            // Truncated like wlroots does
+            Some(scale) => Self {
+                width: (self.width as f64 / scale) as u32,
+                height: (self.height as f64 / scale) as u32,
+            },
+            None => *self,
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, TryFromPrimitive)]
 #[repr(u32)]
 pub enum DisplayOrientation {
     Landscape = 0,
@@ -43,27 +230,117 @@ impl Display for DisplayOrientation {
 
 impl DisplayOrientation {
     pub fn is_same_direction(&self, other: &Self) -> bool {
-        *self as u32 % 2 == *other as u32 % 2
+        (*self as u32).is_multiple_of(2) == (*other as u32).is_multiple_of(2)
+    }
+}
+
+/// Side of another monitor a monitor is placed on, and how both are aligned
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+pub struct Placement {
+    #[serde(flatten)]
+    pub side: Side,
+    /// Edges of both monitors lined up, their top or left edges when missing
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub align: Option<Align>,
+}
+
+impl Display for Placement {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.side.to_string())?;
+        if let Some(align) = self.align {
+            f.write_str(&format!(" aligned {}", align))?;
+        }
+        Ok(())
     }
 }
 
-impl Into<DEVMODE_DISPLAY_ORIENTATION> for DisplayOrientation {
-    fn into(self) -> DEVMODE_DISPLAY_ORIENTATION {
-        DEVMODE_DISPLAY_ORIENTATION(self as u32)
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Side {
+    RightOf(MonitorName),
+    LeftOf(MonitorName),
+    Above(MonitorName),
+    Below(MonitorName),
+}
+
+impl Side {
+    /// Monitor the placement is relative to
+    pub fn reference(&self) -> &MonitorName {
+        match self {
+            Self::RightOf(name) | Self::LeftOf(name) | Self::Above(name) | Self::Below(name) => {
+                name
+            }
+        }
+    }
+
+    pub fn reference_mut(&mut self) -> &mut MonitorName {
+        match self {
+            Self::RightOf(name) | Self::LeftOf(name) | Self::Above(name) | Self::Below(name) => {
+                name
+            }
+        }
+    }
+
+    /// Whether both monitors sit side by side, rather than on top of each other
+    pub fn is_horizontal(&self) -> bool {
+        matches!(self, Self::RightOf(_) | Self::LeftOf(_))
     }
 }
 
+impl Display for Side {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&match self {
+            Self::RightOf(name) => format!("right of {}", name),
+            Self::LeftOf(name) => format!("left of {}", name),
+            Self::Above(name) => format!("above {}", name),
+            Self::Below(name) => format!("below {}", name),
+        })
+    }
+}
+
+/// Top, bottom and center line up monitors side by side, left, right and center the ones on top of each other
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum Align {
+    Top,
+    Bottom,
+    Left,
+    Right,
+    Center,
+}
+
+impl Display for Align {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Top => "top",
+            Self::Bottom => "bottom",
+            Self::Left => "left",
+            Self::Right => "right",
+            Self::Center => "center",
+        })
+    }
+}
+
+#[cfg(windows)]
+impl From<DisplayOrientation> for DEVMODE_DISPLAY_ORIENTATION {
+    fn from(value: DisplayOrientation) -> Self {
+        DEVMODE_DISPLAY_ORIENTATION(value as u32)
+    }
+}
+
+#[cfg(windows)]
 impl From<DEVMODE_DISPLAY_ORIENTATION> for DisplayOrientation {
     fn from(value: DEVMODE_DISPLAY_ORIENTATION) -> Self {
         DisplayOrientation::try_from(value.0).unwrap()
     }
 }
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
 pub struct Pos {
     pub x: i32,
     pub y: i32,
 }
 
+#[cfg(windows)]
 impl From<POINTL> for Pos {
     fn from(value: POINTL) -> Self {
         Self {
@@ -73,35 +350,63 @@ impl From<POINTL> for Pos {
     }
 }
 
-impl Into<POINTL> for Pos {
-    fn into(self) -> POINTL {
+#[cfg(windows)]
+impl From<Pos> for POINTL {
+    fn from(value: Pos) -> Self {
         POINTL {
-            x: self.x,
-            y: self.y,
+            x: value.x,
+            y: value.y,
         }
     }
 }
 
+#[cfg(windows)]
 impl From<DEVMODEW> for DisplaySettings {
     fn from(dm: DEVMODEW) -> Self {
         unsafe {
             let position = dm.Anonymous1.Anonymous2.dmPosition.into();
             let orientation = dm.Anonymous1.Anonymous2.dmDisplayOrientation.into();
+            let resolution = Resolution {
+                width: dm.dmPelsWidth,
+                height: dm.dmPelsHeight,
+            }
+            // dmPels* are rotated with the monitor when queried with EDS_ROTATEDMODE
+            .oriented(orientation);
 
             Self {
                 orientation,
                 position,
+                placement: None,
+                resolution: Some(resolution),
+                refresh_rate: Some(dm.dmDisplayFrequency),
+                scale: None,
+                flipped: false,
+                // Only the display device tells, see the Windows backend
+                primary: false,
+                enabled: true,
+                mirror: None,
             }
         }
     }
 }
 
-impl Into<DEVMODEW> for DisplaySettings {
-    fn into(self) -> DEVMODEW {
-        let mut dm = DEVMODEW::default();
-        dm.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
-        dm.Anonymous1.Anonymous2.dmDisplayOrientation = self.orientation.into();
-        dm.Anonymous1.Anonymous2.dmPosition = self.position.into();
+#[cfg(windows)]
+impl From<DisplaySettings> for DEVMODEW {
+    fn from(value: DisplaySettings) -> Self {
+        let mut dm = DEVMODEW {
+            dmSize: std::mem::size_of::<DEVMODEW>() as u16,
+            ..Default::default()
+        };
+        dm.Anonymous1.Anonymous2.dmDisplayOrientation = value.orientation.into();
+        dm.Anonymous1.Anonymous2.dmPosition = value.position.into();
+        if let Some(resolution) = value.resolution {
+            let resolution = resolution.oriented(value.orientation);
+            dm.dmPelsWidth = resolution.width;
+            dm.dmPelsHeight = resolution.height;
+        }
+        if let Some(refresh_rate) = value.refresh_rate {
+            dm.dmDisplayFrequency = refresh_rate;
+        }
         dm
     }
 }
@@ -0,0 +1,374 @@
+use crate::{
+    backend::MonitorDevice,
+    display_settings::{DisplayMode, Resolution},
+    error::ThisError,
+    monitor::MonitorName,
+};
+use color_eyre::eyre::Result;
+use std::fmt::Display;
+#[cfg(not(windows))]
+use std::fs;
+
+/// Size of the base EDID block, extension blocks are ignored
+const BLOCK_SIZE: usize = 128;
+const HEADER: [u8; 8] = [0x00, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0xFF, 0x00];
+/// Offsets of the 4 descriptors of the base block
+const DESCRIPTORS: [usize; 4] = [54, 72, 90, 108];
+const DESCRIPTOR_SIZE: usize = 18;
+const TAG_SERIAL: u8 = 0xFF;
+const TAG_MODEL_NAME: u8 = 0xFC;
+/// PNP IDs of the manufacturers named after the PNP ID registry, as Wayland compositors report them
+#[cfg(not(windows))]
+const PNP_IDS: [(&str, &str); 23] = [
+    ("ACI", "Ancor Communications Inc"),
+    ("ACR", "Acer Technologies"),
+    ("AOC", "AOC International (USA) Ltd."),
+    ("APP", "Apple Computer Inc"),
+    ("AUO", "AU Optronics"),
+    ("AUS", "ASUSTek COMPUTER INC"),
+    ("BNQ", "BenQ Corporation"),
+    ("BOE", "BOE"),
+    ("CMN", "Chimei Innolux Corporation"),
+    ("DEL", "Dell Inc."),
+    ("ENC", "Eizo Nanao Corporation"),
+    ("GBT", "GIGA-BYTE TECHNOLOGY CO., LTD."),
+    ("GSM", "Goldstar Company Ltd"),
+    ("HWP", "Hewlett Packard"),
+    ("IVM", "Iiyama North America"),
+    ("LEN", "Lenovo Group Limited"),
+    ("LGD", "LG Display"),
+    ("MSI", "Microstep"),
+    ("PHL", "Philips Consumer Electronics Company"),
+    ("SAM", "Samsung Electric Company"),
+    ("SDC", "Samsung Display Corp"),
+    ("SHP", "Sharp Corporation"),
+    ("VSC", "ViewSonic Corporation"),
+];
+
+/// What a monitor tells about itself through its EDID
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Edid {
+    /// 3 letters PNP ID of the manufacturer (`DEL`, `GSM`...)
+    pub manufacturer: String,
+    pub product_code: u16,
+    /// Serial number of the header, 0 when unset
+    pub serial_number: u32,
+    /// Serial number descriptor, usually the one printed on the monitor
+    pub serial: Option<String>,
+    /// Model name descriptor (`DELL U2720Q`...)
+    pub model_name: Option<String>,
+    /// Physical width and height of the picture in millimeters
+    pub size_mm: Option<(u32, u32)>,
+    /// Preferred timing of the monitor, which is its native mode
+    pub native_mode: Option<DisplayMode>,
+}
+
+impl Edid {
+    /// Decodes the base block of an EDID blob
+    pub fn parse(bytes: &[u8]) -> Result<Self> {
+        if bytes.len() < BLOCK_SIZE {
+            Err(ThisError::InvalidEdid("shorter than a block"))?;
+        }
+        let block = &bytes[..BLOCK_SIZE];
+        if block[..8] != HEADER {
+            Err(ThisError::InvalidEdid("wrong header"))?;
+        }
+        if block.iter().fold(0u8, |sum, byte| sum.wrapping_add(*byte)) != 0 {
+            Err(ThisError::InvalidEdid("wrong checksum"))?;
+        }
+
+        // Three letters of 5 bits each, 1 being 'A'
+        let id = u16::from_be_bytes([block[8], block[9]]);
+        let manufacturer = [10, 5, 0]
+            .into_iter()
+            .map(|shift| (b'A' - 1 + ((id >> shift) & 0b11111) as u8) as char)
+            .collect();
+
+        let mut edid = Self {
+            manufacturer,
+            product_code: u16::from_le_bytes([block[10], block[11]]),
+            serial_number: u32::from_le_bytes([block[12], block[13], block[14], block[15]]),
+            serial: None,
+            model_name: None,
+            // The header only has the size in centimeters, 0 when unknown
+            size_mm: (block[21] != 0 && block[22] != 0)
+                .then(|| (block[21] as u32 * 10, block[22] as u32 * 10)),
+            native_mode: None,
+        };
+
+        for (index, offset) in DESCRIPTORS.into_iter().enumerate() {
+            let descriptor = &block[offset..offset + DESCRIPTOR_SIZE];
+            if descriptor[0] != 0 || descriptor[1] != 0 {
+                // The first detailed timing is the preferred one
+                if index == 0 {
+                    edid.read_detailed_timing(descriptor);
+                }
+                continue;
+            }
+
+            match descriptor[3] {
+                TAG_SERIAL => edid.serial = descriptor_text(descriptor),
+                TAG_MODEL_NAME => edid.model_name = descriptor_text(descriptor),
+                _ => {}
+            }
+        }
+
+        Ok(edid)
+    }
+
+    fn read_detailed_timing(&mut self, descriptor: &[u8]) {
+        // Pixel clock in 10 kHz units
+        let pixel_clock = u16::from_le_bytes([descriptor[0], descriptor[1]]) as u64 * 10_000;
+        let h_active = descriptor[2] as u32 | (descriptor[4] as u32 & 0xF0) << 4;
+        let h_blank = descriptor[3] as u32 | (descriptor[4] as u32 & 0x0F) << 8;
+        let v_active = descriptor[5] as u32 | (descriptor[7] as u32 & 0xF0) << 4;
+        let v_blank = descriptor[6] as u32 | (descriptor[7] as u32 & 0x0F) << 8;
+
+        let total = (h_active + h_blank) as u64 * (v_active + v_blank) as u64;
+        if let Some(refresh_rate) = (pixel_clock + total / 2).checked_div(total) {
+            self.native_mode = Some(DisplayMode {
+                resolution: Resolution {
+                    width: h_active,
+                    height: v_active,
+                },
+                refresh_rate: refresh_rate as u32,
+                bits_per_pixel: None,
+            });
+        }
+
+        let width = descriptor[12] as u32 | (descriptor[14] as u32 & 0xF0) << 4;
+        let height = descriptor[13] as u32 | (descriptor[14] as u32 & 0x0F) << 8;
+        if width != 0 && height != 0 {
+            self.size_mm = Some((width, height));
+        }
+    }
+
+    /// Names the monitor after its manufacturer, product code and serial number, see [`MonitorDevice::stable_name`]
+    ///
+    /// Panels leaving the serial number of the header unset often have a serial number descriptor, which is used instead
+    pub fn stable_name(&self) -> Option<MonitorName> {
+        let serial = match (self.serial_number, &self.serial) {
+            (0, Some(serial)) => serial.clone(),
+            (0, None) => String::new(),
+            (serial_number, _) => serial_number.to_string(),
+        };
+        MonitorDevice::stable_name(
+            &self.manufacturer,
+            &format!("{:04X}", self.product_code),
+            &serial,
+        )
+    }
+
+    /// EDID of a DRM connector (`DP-1`...) of any graphics card, read from sysfs
+    #[cfg(not(windows))]
+    pub fn from_sysfs(connector: &str) -> Option<Self> {
+        fs::read_dir("/sys/class/drm")
+            .ok()?
+            .flatten()
+            .filter(|entry| {
+                entry
+                    .file_name()
+                    .to_str()
+                    .and_then(|name| name.split_once('-'))
+                    .is_some_and(|(card, name)| card.starts_with("card") && name == connector)
+            })
+            // Disconnected connectors have an empty EDID
+            .find_map(|entry| Self::parse(&fs::read(entry.path().join("edid")).ok()?).ok())
+    }
+
+    /// Names a monitor after the make, model and serial a compositor reports, as close to [`Edid::stable_name`] as they allow
+    ///
+    /// Makes are brought back to their PNP ID, product codes and serial numbers given in hexadecimal to the form of the EDID.
+    /// Model name and serial number descriptors take the place of the product code and serial number when the monitor has them
+    #[cfg(not(windows))]
+    pub fn stable_name_from_text(make: &str, model: &str, serial: &str) -> Option<MonitorName> {
+        let hex = |text: &str| {
+            let text = text.trim();
+            let digits = text.strip_prefix("0x").or(text.strip_prefix("0X"))?;
+            u32::from_str_radix(digits, 16).ok()
+        };
+
+        let make = make.trim();
+        let manufacturer = PNP_IDS
+            .iter()
+            .find(|(_, name)| name.eq_ignore_ascii_case(make))
+            .map_or(make, |(id, _)| id);
+        let product = match hex(model) {
+            Some(product_code) => format!("{:04X}", product_code),
+            None => model.to_string(),
+        };
+        let serial = match hex(serial) {
+            Some(0) => String::new(),
+            Some(serial_number) => serial_number.to_string(),
+            None => serial.to_string(),
+        };
+        MonitorDevice::stable_name(manufacturer, &product, &serial)
+    }
+
+    /// Diagonal of the picture in inches
+    pub fn diagonal(&self) -> Option<f64> {
+        self.size_mm
+            .map(|(width, height)| (width as f64).hypot(height as f64) / 25.4)
+    }
+}
+
+impl Display for Edid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.model_name {
+            Some(model_name) => f.write_str(model_name)?,
+            None => f.write_str(&format!("{} {:04X}", self.manufacturer, self.product_code))?,
+        }
+        if let (Some(diagonal), Some((width, height))) = (self.diagonal(), self.size_mm) {
+            f.write_str(&format!(" {:.0}\" ({}x{} mm)", diagonal, width, height))?;
+        }
+        if let Some(mode) = self.native_mode {
+            f.write_str(&format!(", native {}", mode))?;
+        }
+        if let Some(serial) = &self.serial {
+            f.write_str(&format!(", serial {}", serial))?;
+        }
+        Ok(())
+    }
+}
+
+/// Text of a display descriptor, ended by a line feed and padded with spaces
+fn descriptor_text(descriptor: &[u8]) -> Option<String> {
+    let text = &descriptor[5..];
+    let end = text.iter().position(|c| *c == b'\n').unwrap_or(text.len());
+    let text = String::from_utf8_lossy(&text[..end]).trim().to_string();
+    (!text.is_empty()).then_some(text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DELL_U2720Q: &[u8] = include_bytes!("../fixtures/edid/dell_u2720q.bin");
+    const LG_27GL850: &[u8] = include_bytes!("../fixtures/edid/lg_27gl850.bin");
+
+    /// Sets the checksum byte of the base block of an edited blob
+    fn with_checksum(mut bytes: Vec<u8>) -> Vec<u8> {
+        let sum = bytes[..BLOCK_SIZE - 1]
+            .iter()
+            .fold(0u8, |sum, byte| sum.wrapping_add(*byte));
+        bytes[BLOCK_SIZE - 1] = 0u8.wrapping_sub(sum);
+        bytes
+    }
+
+    #[test]
+    fn parses_dell_u2720q() {
+        let edid = Edid::parse(DELL_U2720Q).unwrap();
+        assert_eq!(edid.manufacturer, "DEL");
+        assert_eq!(edid.product_code, 0xA0FE);
+        assert_eq!(edid.serial_number, 1279347248);
+        assert_eq!(edid.serial.as_deref(), Some("7RQKR13"));
+        assert_eq!(edid.model_name.as_deref(), Some("DELL U2720Q"));
+        assert_eq!(edid.size_mm, Some((597, 336)));
+        assert_eq!(
+            edid.native_mode,
+            Some(DisplayMode {
+                resolution: Resolution {
+                    width: 3840,
+                    height: 2160,
+                },
+                refresh_rate: 60,
+                bits_per_pixel: None,
+            })
+        );
+    }
+
+    #[test]
+    fn parses_lg_27gl850() {
+        let edid = Edid::parse(LG_27GL850).unwrap();
+        assert_eq!(edid.manufacturer, "GSM");
+        assert_eq!(edid.product_code, 0x5B7F);
+        assert_eq!(edid.serial_number, 237797);
+        assert_eq!(edid.serial.as_deref(), Some("907NTGYAB123"));
+        assert_eq!(edid.model_name.as_deref(), Some("LG 27GL850"));
+        assert_eq!(edid.size_mm, Some((597, 336)));
+        assert_eq!(
+            edid.native_mode,
+            Some(DisplayMode {
+                resolution: Resolution {
+                    width: 2560,
+                    height: 1440,
+                },
+                refresh_rate: 60,
+                bits_per_pixel: None,
+            })
+        );
+    }
+
+    #[test]
+    fn rejects_bad_checksum() {
+        let mut bytes = DELL_U2720Q.to_vec();
+        bytes[BLOCK_SIZE - 1] = bytes[BLOCK_SIZE - 1].wrapping_add(1);
+        let err = Edid::parse(&bytes).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid EDID: wrong checksum");
+    }
+
+    #[test]
+    fn rejects_truncated_block() {
+        let err = Edid::parse(&DELL_U2720Q[..100]).unwrap_err();
+        assert_eq!(err.to_string(), "Invalid EDID: shorter than a block");
+    }
+
+    #[test]
+    fn stable_name_uses_header_serial_number() {
+        let edid = Edid::parse(DELL_U2720Q).unwrap();
+        assert_eq!(edid.stable_name().as_deref(), Some("DEL A0FE 1279347248"));
+    }
+
+    #[test]
+    fn stable_name_falls_back_to_serial_descriptor() {
+        let mut bytes = DELL_U2720Q.to_vec();
+        bytes[12..16].fill(0);
+        let edid = Edid::parse(&with_checksum(bytes)).unwrap();
+        assert_eq!(edid.serial_number, 0);
+        assert_eq!(edid.stable_name().as_deref(), Some("DEL A0FE 7RQKR13"));
+    }
+
+    #[test]
+    fn identical_panels_are_told_apart_by_serial_descriptor() {
+        let panel = |serial: &[u8; 7]| {
+            let mut bytes = DELL_U2720Q.to_vec();
+            bytes[12..16].fill(0);
+            // Serial number descriptor is the second one
+            bytes[72 + 5..72 + 12].copy_from_slice(serial);
+            Edid::parse(&with_checksum(bytes)).unwrap()
+        };
+        assert_ne!(
+            panel(b"AAAAAAA").stable_name(),
+            panel(b"BBBBBBB").stable_name()
+        );
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn compositor_names_match_edid_names() {
+        let edid = Edid::parse(LG_27GL850).unwrap();
+        // wlroots names the manufacturer, Mutter gives its PNP ID
+        for (make, model, serial) in [
+            ("Goldstar Company Ltd", "0x5B7F", "0x0003A0E5"),
+            ("GSM", "0x5b7f", "0x0003a0e5"),
+        ] {
+            assert_eq!(
+                Edid::stable_name_from_text(make, model, serial),
+                edid.stable_name()
+            );
+        }
+    }
+
+    #[test]
+    #[cfg(not(windows))]
+    fn compositor_names_keep_descriptors_and_unknown_makes() {
+        assert_eq!(
+            Edid::stable_name_from_text("Dell Inc.", "DELL U2720Q", "7RQKR13").as_deref(),
+            Some("DEL DELL U2720Q 7RQKR13")
+        );
+        assert_eq!(
+            Edid::stable_name_from_text("Unknown", "0x1234", "0x00000000").as_deref(),
+            Some("Unknown 1234")
+        );
+    }
+}
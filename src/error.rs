@@ -1,14 +1,72 @@
+use crate::monitor::MonitorName;
+use color_eyre::eyre::Report;
 use thiserror::Error;
+#[cfg(windows)]
 use windows::Win32::Graphics::Gdi::DISP_CHANGE;
 
 #[derive(Debug, Error)]
 pub enum ThisError {
+    #[cfg(windows)]
     #[error("Failed to change display settings")]
     DispChange(DISP_CHANGE),
+    #[cfg(not(windows))]
+    #[error("Failed to change display settings: {0}")]
+    ChangeSettings(String),
+    #[error("Failed to change the display settings of {monitor}: {reason}")]
+    ChangeMonitorSettings {
+        monitor: MonitorName,
+        reason: String,
+    },
+    #[error("{0}, the previous display settings were restored")]
+    RolledBack(Report),
+    #[error("{0}, restoring the previous display settings failed too: {1}")]
+    RollbackFailed(Report, Report),
     #[error("Failed to fetch display settings")]
     EnumDisplaySettings,
+    #[error("Unsupported display modes: {}", .0.join(", "))]
+    UnsupportedModes(Vec<String>),
+    #[error("Invalid mirror: {0}")]
+    InvalidMirror(String),
+    #[error("Invalid placement: {0}")]
+    InvalidPlacement(String),
+    #[error("No resolution is supported by every monitor of {}", .0.join(", "))]
+    NoCommonResolution(Vec<String>),
     #[error("Monitor not found for preset")]
     MonitorNotFound,
     #[error("Failed to parse keybind")]
     KeybindParsingFailed,
+    #[error("Invalid EDID: {0}")]
+    InvalidEdid(&'static str),
+    #[error("No config folder found, the presets file can be set with --config or WIMLAM_CONFIG")]
+    NoConfigDir,
+    #[error("{0} needs a value")]
+    MissingArgument(&'static str),
+    #[error("Unknown argument {0}")]
+    UnknownArgument(String),
+    #[error("{0} and {1} can't be used together")]
+    ConflictingArguments(&'static str, &'static str),
+    #[error("{0} only goes along with {1}")]
+    ArgumentWithout(&'static str, &'static str),
+    #[error("Invalid value {value}, expected {expected}")]
+    InvalidArgument {
+        value: String,
+        expected: &'static str,
+    },
+    #[error("No preset named {0}")]
+    PresetNotFound(String),
+    #[error("Presets named {} exist already, choose to rename, replace or skip them with --on-conflict", .0.join(", "))]
+    ImportConflicts(Vec<String>),
+    #[error("No presets file at {}", .0.display())]
+    ConfigNotFound(std::path::PathBuf),
+    #[error("{} exists already", .0.display())]
+    FileExists(std::path::PathBuf),
+    #[error("Invalid presets file: {0}")]
+    InvalidConfig(&'static str),
+    #[error("The presets file is of version {0}, written by a newer version of WiMLaM")]
+    ConfigTooNew(u32),
+    #[cfg(not(windows))]
+    #[error("zenity isn't installed, it's needed to ask questions outside of a terminal")]
+    ZenityMissing,
+    #[error("Unknown display backend {0}")]
+    UnknownBackend(String),
 }
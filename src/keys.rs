@@ -0,0 +1,209 @@
+#[cfg(windows)]
+pub use win_hotkeys::VKey;
+
+#[cfg(not(windows))]
+pub use self::{fallback::VKey, x11::HotkeyManager};
+
+#[cfg(not(windows))]
+mod fallback {
+    use crate::error::ThisError;
+    use std::fmt::Display;
+
+    /// Stand-in for `win_hotkeys::VKey` off Windows, keeps the virtual key name (without the `VK_` prefix)
+    /// so keybinds written on Windows keep loading and displaying the same way
+    #[derive(Debug, Clone, PartialEq, Eq, Hash)]
+    pub struct VKey(String);
+
+    impl VKey {
+        pub fn from_keyname(name: &str) -> Result<Self, ThisError> {
+            let name = name.to_ascii_uppercase();
+            let name = name.trim_start_matches("VK_");
+            if name.is_empty() || name.contains(char::is_whitespace) {
+                return Err(ThisError::KeybindParsingFailed);
+            }
+
+            Ok(Self(
+                match name {
+                    "CTRL" => "CONTROL",
+                    "ALT" => "MENU",
+                    "WIN" => "LWIN",
+                    other => other,
+                }
+                .to_string(),
+            ))
+        }
+
+        pub fn name(&self) -> &str {
+            &self.0
+        }
+    }
+
+    impl Display for VKey {
+        fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+            write!(f, "VK_{}", self.0)
+        }
+    }
+}
+
+#[cfg(not(windows))]
+mod x11 {
+    use crate::{error::ThisError, keys::VKey};
+    use color_eyre::eyre::Result;
+    use x11rb::{
+        connection::Connection,
+        protocol::{
+            Event,
+            xproto::{ConnectionExt as _, GrabMode, Keycode, Keysym, ModMask},
+        },
+    };
+
+    struct Hotkey<T> {
+        keysym: Keysym,
+        modifiers: u16,
+        callback: Box<dyn Fn() -> T + Send>,
+    }
+
+    /// Global hotkeys grabbed on the X11 root window, mirrors `win_hotkeys::HotkeyManager`
+    pub struct HotkeyManager<T> {
+        hotkeys: Vec<Hotkey<T>>,
+    }
+
+    impl<T> HotkeyManager<T> {
+        pub fn new() -> Self {
+            Self {
+                hotkeys: Vec::new(),
+            }
+        }
+
+        pub fn register_hotkey(
+            &mut self,
+            trigger_key: VKey,
+            mod_keys: &[VKey],
+            callback: impl Fn() -> T + Send + 'static,
+        ) -> Result<usize> {
+            let modifiers = mod_keys
+                .iter()
+                .map(|key| modifier_mask(key).ok_or(ThisError::KeybindParsingFailed))
+                .try_fold(0, |mask, modifier| Ok::<_, ThisError>(mask | modifier?))?;
+
+            self.hotkeys.push(Hotkey {
+                keysym: keysym(&trigger_key).ok_or(ThisError::KeybindParsingFailed)?,
+                modifiers,
+                callback: Box::new(callback),
+            });
+            Ok(self.hotkeys.len() - 1)
+        }
+
+        pub fn event_loop(&mut self) -> Result<()> {
+            let (conn, screen_num) = x11rb::connect(None)?;
+            let root = conn.setup().roots[screen_num].root;
+            let (min_keycode, max_keycode) = (conn.setup().min_keycode, conn.setup().max_keycode);
+            let mapping = conn
+                .get_keyboard_mapping(min_keycode, max_keycode - min_keycode + 1)?
+                .reply()?;
+
+            let keycodes: Vec<Keycode> = self
+                .hotkeys
+                .iter()
+                .map(|hotkey| {
+                    mapping
+                        .keysyms
+                        .chunks(mapping.keysyms_per_keycode as usize)
+                        .position(|keysyms| keysyms.contains(&hotkey.keysym))
+                        .map(|index| min_keycode + index as Keycode)
+                        .ok_or(ThisError::KeybindParsingFailed)
+                })
+                .collect::<Result<_, _>>()?;
+
+            // Caps lock and num lock must not prevent a hotkey from triggering
+            let (caps_lock, num_lock) = (u16::from(ModMask::LOCK), u16::from(ModMask::M2));
+            for (hotkey, keycode) in self.hotkeys.iter().zip(&keycodes) {
+                for ignored in [0, caps_lock, num_lock, caps_lock | num_lock] {
+                    conn.grab_key(
+                        false,
+                        root,
+                        ModMask::from(hotkey.modifiers | ignored),
+                        *keycode,
+                        GrabMode::ASYNC,
+                        GrabMode::ASYNC,
+                    )?
+                    .check()?;
+                }
+            }
+            conn.flush()?;
+
+            let relevant_modifiers = u16::from(ModMask::SHIFT)
+                | u16::from(ModMask::CONTROL)
+                | u16::from(ModMask::M1)
+                | u16::from(ModMask::M4);
+
+            loop {
+                if let Event::KeyPress(event) = conn.wait_for_event()? {
+                    let modifiers = u16::from(event.state) & relevant_modifiers;
+                    for (hotkey, keycode) in self.hotkeys.iter().zip(&keycodes) {
+                        if *keycode == event.detail && hotkey.modifiers == modifiers {
+                            (hotkey.callback)();
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn modifier_mask(key: &VKey) -> Option<u16> {
+        Some(u16::from(match key.name() {
+            "SHIFT" | "LSHIFT" | "RSHIFT" => ModMask::SHIFT,
+            "CONTROL" | "LCONTROL" | "RCONTROL" => ModMask::CONTROL,
+            "MENU" | "LMENU" | "RMENU" => ModMask::M1,
+            "LWIN" | "RWIN" => ModMask::M4,
+            _ => return None,
+        }))
+    }
+
+    /// Maps a virtual key name onto the X11 keysym of the same key
+    fn keysym(key: &VKey) -> Option<Keysym> {
+        let name = key.name();
+        let mut chars = name.chars();
+        if let (Some(c), None) = (chars.next(), chars.next()) {
+            return c
+                .is_ascii_alphanumeric()
+                .then_some(c.to_ascii_lowercase() as Keysym);
+        }
+
+        if let Some(n) = name
+            .strip_prefix("NUMPAD")
+            .and_then(|n| n.parse::<u32>().ok())
+        {
+            return (n <= 9).then_some(0xffb0 + n);
+        }
+        if let Some(n) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+            return (1..=24).contains(&n).then_some(0xffbe + n - 1);
+        }
+
+        Some(match name {
+            "BACK" => 0xff08,
+            "TAB" => 0xff09,
+            "RETURN" => 0xff0d,
+            "PAUSE" => 0xff13,
+            "ESCAPE" => 0xff1b,
+            "SPACE" => 0x0020,
+            "HOME" => 0xff50,
+            "LEFT" => 0xff51,
+            "UP" => 0xff52,
+            "RIGHT" => 0xff53,
+            "DOWN" => 0xff54,
+            "PRIOR" => 0xff55,
+            "NEXT" => 0xff56,
+            "END" => 0xff57,
+            "SNAPSHOT" => 0xff61,
+            "INSERT" => 0xff63,
+            "DELETE" => 0xffff,
+            "MULTIPLY" => 0xffaa,
+            "ADD" => 0xffab,
+            "SUBTRACT" => 0xffad,
+            "DECIMAL" => 0xffae,
+            "DIVIDE" => 0xffaf,
+            _ => return None,
+        })
+    }
+}
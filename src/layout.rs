@@ -0,0 +1,326 @@
+use crate::{
+    display_settings::{Pos, Resolution},
+    monitor::MonitorName,
+};
+use std::fmt::Display;
+
+/// Area of the desktop covered by a monitor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rect {
+    pub name: MonitorName,
+    pub position: Pos,
+    /// Size once rotated
+    pub size: Resolution,
+}
+
+impl Rect {
+    fn right(&self) -> i32 {
+        self.position.x + self.size.width as i32
+    }
+
+    fn bottom(&self) -> i32 {
+        self.position.y + self.size.height as i32
+    }
+
+    /// Size of the area both monitors cover, none when they don't overlap
+    fn overlap(&self, other: &Self) -> Option<Resolution> {
+        let width = self.right().min(other.right()) - self.position.x.max(other.position.x);
+        let height = self.bottom().min(other.bottom()) - self.position.y.max(other.position.y);
+        (width > 0 && height > 0).then_some(Resolution {
+            width: width as u32,
+            height: height as u32,
+        })
+    }
+
+    /// Whether the cursor can go from one monitor to the other, they share a piece of edge or overlap
+    fn touches(&self, other: &Self) -> bool {
+        let shared_width = self.right().min(other.right()) - self.position.x.max(other.position.x);
+        let shared_height =
+            self.bottom().min(other.bottom()) - self.position.y.max(other.position.y);
+        shared_width >= 0 && shared_height >= 0 && (shared_width > 0 || shared_height > 0)
+    }
+}
+
+/// Something wrong with a layout, that the backend may refuse or change into another layout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Diagnostic {
+    Overlap {
+        monitors: (MonitorName, MonitorName),
+        size: Resolution,
+    },
+    /// The monitor can't be reached from the one at 0,0 by moving the cursor
+    Unreachable(MonitorName),
+    NoMonitorAtOrigin,
+    /// The monitor sits further left or up than its own size from 0,0
+    FarFromOrigin {
+        monitor: MonitorName,
+        position: Pos,
+    },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Overlap {
+                monitors: (a, b),
+                size,
+            } => write!(f, "{} and {} overlap on {} pixels", a, b, size),
+            Self::Unreachable(monitor) => write!(
+                f,
+                "{} doesn't touch the other monitors, the cursor can't reach it",
+                monitor
+            ),
+            Self::NoMonitorAtOrigin => f.write_str("No monitor sits at 0,0"),
+            Self::FarFromOrigin { monitor, position } => write!(
+                f,
+                "{} sits more than a screen away from 0,0, at {}/{}",
+                monitor, position.x, position.y
+            ),
+        }
+    }
+}
+
+/// Checks the monitors of a layout don't overlap, can all be reached and start from 0,0
+pub fn check(rects: &[Rect]) -> Vec<Diagnostic> {
+    let mut diagnostics = Vec::new();
+
+    for (index, rect) in rects.iter().enumerate() {
+        for other in &rects[index + 1..] {
+            if let Some(size) = rect.overlap(other) {
+                diagnostics.push(Diagnostic::Overlap {
+                    monitors: (rect.name.clone(), other.name.clone()),
+                    size,
+                });
+            }
+        }
+    }
+
+    let origin = Pos { x: 0, y: 0 };
+    let start = rects.iter().position(|rect| rect.position == origin);
+    if start.is_none() && !rects.is_empty() {
+        diagnostics.push(Diagnostic::NoMonitorAtOrigin);
+    }
+
+    // Monitors reached from the one at 0,0, or the first one without it
+    let mut reached = vec![false; rects.len()];
+    let mut queue: Vec<usize> = rects
+        .first()
+        .map(|_| start.unwrap_or(0))
+        .into_iter()
+        .collect();
+    while let Some(index) = queue.pop() {
+        if reached[index] {
+            continue;
+        }
+        reached[index] = true;
+        queue.extend(
+            (0..rects.len())
+                .filter(|other| !reached[*other] && rects[index].touches(&rects[*other])),
+        );
+    }
+    diagnostics.extend(
+        rects
+            .iter()
+            .zip(reached)
+            .filter(|(_, reached)| !reached)
+            .map(|(rect, _)| Diagnostic::Unreachable(rect.name.clone())),
+    );
+
+    diagnostics.extend(
+        rects
+            .iter()
+            .filter(|rect| {
+                rect.position.x < -(rect.size.width as i32)
+                    || rect.position.y < -(rect.size.height as i32)
+            })
+            .map(|rect| Diagnostic::FarFromOrigin {
+                monitor: rect.name.clone(),
+                position: rect.position,
+            }),
+    );
+
+    diagnostics
+}
+
+/// Distance in pixels under which the edges of two monitors are snapped together
+pub const SNAP_TOLERANCE: i32 = 8;
+
+/// Snaps edges closer than [`SNAP_TOLERANCE`], pushes overlapping monitors apart,
+/// then moves the layout so the primary monitor, or else the top left one, sits at 0,0
+///
+/// Monitors are settled one after the other from the closest to that one, each against the ones already settled.
+/// Returns how far the layout was moved
+pub fn normalize(rects: &mut [Rect], primary: Option<&MonitorName>) -> Pos {
+    let Some(anchor) = rects
+        .iter()
+        .position(|rect| Some(&rect.name) == primary)
+        .or_else(|| {
+            (0..rects.len())
+                .min_by_key(|index| (rects[*index].position.y, rects[*index].position.x))
+        })
+    else {
+        return Pos { x: 0, y: 0 };
+    };
+    let origin = rects[anchor].position;
+
+    let mut order: Vec<usize> = (0..rects.len()).collect();
+    order.sort_by_key(|index| {
+        let position = rects[*index].position;
+        (
+            *index != anchor,
+            (position.x - origin.x).abs() + (position.y - origin.y).abs(),
+        )
+    });
+    for (settled, index) in order.iter().enumerate() {
+        let others: Vec<Rect> = order[..settled]
+            .iter()
+            .map(|other| rects[*other].clone())
+            .collect();
+        snap(&mut rects[*index], &others);
+        push_out(&mut rects[*index], &others);
+    }
+
+    for rect in rects.iter_mut() {
+        rect.position.x -= origin.x;
+        rect.position.y -= origin.y;
+    }
+    origin
+}
+
+/// Moves a monitor by the smallest distance lining up one of its edges with an edge of another monitor, on each axis
+fn snap(rect: &mut Rect, others: &[Rect]) {
+    let closest = |edges: [i32; 2], other_edges: &dyn Fn(&Rect) -> [i32; 2]| {
+        others
+            .iter()
+            .flat_map(other_edges)
+            .flat_map(|other_edge| edges.map(|edge| other_edge - edge))
+            .filter(|distance| *distance != 0 && distance.abs() <= SNAP_TOLERANCE)
+            .min_by_key(|distance| distance.abs())
+            .unwrap_or(0)
+    };
+
+    let x = closest([rect.position.x, rect.right()], &|other| {
+        [other.position.x, other.right()]
+    });
+    let y = closest([rect.position.y, rect.bottom()], &|other| {
+        [other.position.y, other.bottom()]
+    });
+    rect.position.x += x;
+    rect.position.y += y;
+}
+
+/// Moves a monitor out of the ones it overlaps, along the axis they overlap the least on
+fn push_out(rect: &mut Rect, others: &[Rect]) {
+    // Pushing a monitor out of one may push it into another, give up past a try per side of every monitor
+    for _ in 0..=others.len() * 4 {
+        let Some((other, overlap)) = others
+            .iter()
+            .find_map(|other| rect.overlap(other).map(|overlap| (other, overlap)))
+        else {
+            return;
+        };
+
+        if overlap.width <= overlap.height {
+            let center = rect.position.x * 2 + rect.size.width as i32;
+            let other_center = other.position.x * 2 + other.size.width as i32;
+            rect.position.x = if center >= other_center {
+                other.right()
+            } else {
+                other.position.x - rect.size.width as i32
+            };
+        } else {
+            let center = rect.position.y * 2 + rect.size.height as i32;
+            let other_center = other.position.y * 2 + other.size.height as i32;
+            rect.position.y = if center >= other_center {
+                other.bottom()
+            } else {
+                other.position.y - rect.size.height as i32
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rect(name: &str, x: i32, y: i32, width: u32, height: u32) -> Rect {
+        Rect {
+            name: MonitorName::from(name),
+            position: Pos { x, y },
+            size: Resolution { width, height },
+        }
+    }
+
+    #[test]
+    fn accepts_monitors_side_by_side() {
+        assert!(
+            check(&[
+                rect("LAPTOP", 0, 0, 1920, 1080),
+                rect("DESK", 1920, 0, 2560, 1440),
+                rect("TV", -1920, 500, 1920, 1080),
+            ])
+            .is_empty()
+        );
+        assert!(check(&[]).is_empty());
+    }
+
+    #[test]
+    fn finds_overlaps() {
+        assert_eq!(
+            check(&[
+                rect("LAPTOP", 0, 0, 1920, 1080),
+                rect("DESK", 1900, 100, 2560, 1440),
+            ]),
+            [Diagnostic::Overlap {
+                monitors: (MonitorName::from("LAPTOP"), MonitorName::from("DESK")),
+                size: Resolution {
+                    width: 20,
+                    height: 980,
+                },
+            }]
+        );
+    }
+
+    #[test]
+    fn finds_unreachable_monitors() {
+        // Touching on a corner only isn't enough for the cursor
+        assert_eq!(
+            check(&[
+                rect("LAPTOP", 0, 0, 1920, 1080),
+                rect("DESK", 1930, 0, 2560, 1440),
+                rect("TV", -1920, 1080, 1920, 1080),
+            ]),
+            [
+                Diagnostic::Unreachable(MonitorName::from("DESK")),
+                Diagnostic::Unreachable(MonitorName::from("TV")),
+            ]
+        );
+    }
+
+    #[test]
+    fn finds_layouts_without_a_monitor_at_the_origin() {
+        assert_eq!(
+            check(&[
+                rect("LAPTOP", 10, 0, 1920, 1080),
+                rect("DESK", 1930, 0, 2560, 1440),
+            ]),
+            [Diagnostic::NoMonitorAtOrigin]
+        );
+    }
+
+    #[test]
+    fn finds_monitors_far_from_the_origin() {
+        assert_eq!(
+            check(&[
+                rect("LAPTOP", 0, 0, 1920, 1080),
+                rect("DESK", -2560, 0, 2560, 1440),
+                rect("TV", -4480, 0, 1920, 1080),
+            ]),
+            [Diagnostic::FarFromOrigin {
+                monitor: MonitorName::from("TV"),
+                position: Pos { x: -4480, y: 0 },
+            }]
+        );
+    }
+}
@@ -1,63 +1,365 @@
 mod app;
+mod backend;
+mod config;
+mod confirm;
 mod display_settings;
+mod edid;
 mod error;
+mod keys;
+mod layout;
 mod monitor;
 mod preset;
+mod transfer;
 
-use crate::{app::App, monitor::Monitor, preset::Preset};
-use color_eyre::{self, eyre::Result};
+#[cfg(not(windows))]
+use crate::keys::HotkeyManager;
+use crate::{
+    app::App,
+    error::ThisError,
+    monitor::Monitor,
+    preset::{ApplyOutcome, Keybind, Preset},
+    transfer::OnConflict,
+};
+use color_eyre::{
+    self,
+    eyre::{Report, Result},
+};
 use once_cell::sync::Lazy;
 use std::{
-    path::PathBuf,
+    fs,
+    path::{Path, PathBuf},
     process,
     sync::{Arc, Mutex},
 };
 use sysinfo::System;
 use sysinfo::{ProcessRefreshKind, RefreshKind};
+#[cfg(windows)]
 use win_hotkeys::HotkeyManager;
 
 pub static DATA: Lazy<Mutex<Data>> = Lazy::new(|| Mutex::new(Data::default()));
-pub const SYS_SPECIFCS: Lazy<RefreshKind> = Lazy::new(|| {
+pub static SYS_SPECIFCS: Lazy<RefreshKind> = Lazy::new(|| {
     RefreshKind::nothing()
         .with_processes(ProcessRefreshKind::nothing().with_exe(sysinfo::UpdateKind::Always))
 });
 
 fn main() -> Result<()> {
-    let args: Vec<String> = std::env::args().collect();
-    let headless = args.contains(&String::from(App::HEADLESS_ARG));
+    let args = Args::parse(std::env::args().skip(1))?;
+    let config_path = config::path(args.config.as_deref())?;
+    if let Command::Convert(target) = &args.command {
+        return run_convert(&config_path, target);
+    }
 
-    let monitors = Monitor::get_monitors();
-    let presets: Vec<Arc<Mutex<Preset>>> = Preset::read()?
+    let monitors = Monitor::get_monitors(&backend::from_env()?)?;
+    let mut presets = match config::read(&config_path) {
+        Ok(presets) => presets,
+        Err(err) => restore_presets(&config_path, err)?,
+    };
+    // Presets saved before monitors had a stable name are keyed on the backend's one
+    let mut migrated = false;
+    for preset in &mut presets {
+        migrated |= preset.migrate_names(&monitors);
+    }
+    let presets: Vec<Arc<Mutex<Preset>>> = presets
         .into_iter()
         .map(|preset| Arc::new(Mutex::new(preset)))
         .collect();
 
-    *DATA.lock().unwrap() = Data { monitors, presets };
+    *DATA.lock().unwrap() = Data {
+        monitors,
+        presets,
+        config_path,
+    };
+    if migrated {
+        DATA.lock().unwrap().save()?;
+    }
+
+    match args.command {
+        Command::Export { target, names } => return run_export(&target, &names),
+        Command::Import { source } => return run_import(&source, args.on_conflict),
+        Command::DryRun => return run_dry_run(),
+        Command::Check => return run_check(),
+        Command::App | Command::Headless | Command::Convert(_) => {}
+    }
 
     let mut sys = System::new_with_specifics(*SYS_SPECIFCS);
     kill_others(&mut sys);
 
-    if headless {
-        let mut hkm: HotkeyManager<Result<()>> = HotkeyManager::new();
-        let presets = DATA.lock().unwrap().presets.clone();
-        for preset in presets {
-            let p = preset.clone();
-            if let Some(keybind) = &preset.lock().unwrap().keybind {
-                hkm.register_hotkey(keybind.trigger_key, &keybind.modifiers, move || {
-                    let monitors = &DATA.lock().unwrap().monitors;
-                    p.lock().unwrap().apply(monitors)?;
-                    Ok(())
-                })?;
+    if args.command == Command::Headless {
+        run_headless()
+    } else {
+        color_eyre::install()?;
+        ratatui::run(|terminal| App::default().run(terminal))?;
+        Ok(())
+    }
+}
+
+/// What the command line asks for, the app itself when nothing is
+#[derive(Debug, PartialEq)]
+enum Command {
+    App,
+    Headless,
+    DryRun,
+    Check,
+    Convert(PathBuf),
+    /// Every preset when no name is given
+    Export {
+        target: PathBuf,
+        names: Vec<String>,
+    },
+    Import {
+        source: PathBuf,
+    },
+}
+
+#[derive(Debug, PartialEq)]
+struct Args {
+    config: Option<String>,
+    command: Command,
+    on_conflict: Option<OnConflict>,
+}
+
+impl Args {
+    /// Refuses unknown arguments and commands given along with another one
+    fn parse(args: impl IntoIterator<Item = String>) -> Result<Self> {
+        let mut args = args.into_iter().peekable();
+        let mut config = None;
+        let mut on_conflict = None;
+        let mut command: Option<(&'static str, Command)> = None;
+
+        while let Some(arg) = args.next() {
+            let mut value = |name: &'static str| {
+                args.next()
+                    .filter(|value| !value.starts_with("--"))
+                    .ok_or(ThisError::MissingArgument(name))
+            };
+            let (name, new_command) = match arg.as_str() {
+                App::CONFIG_ARG => {
+                    config = Some(value(App::CONFIG_ARG)?);
+                    continue;
+                }
+                App::ON_CONFLICT_ARG => {
+                    on_conflict = Some(value(App::ON_CONFLICT_ARG)?.parse()?);
+                    continue;
+                }
+                App::HEADLESS_ARG => (App::HEADLESS_ARG, Command::Headless),
+                App::DRY_RUN_ARG => (App::DRY_RUN_ARG, Command::DryRun),
+                App::CHECK_ARG => (App::CHECK_ARG, Command::Check),
+                App::CONVERT_ARG => (
+                    App::CONVERT_ARG,
+                    Command::Convert(value(App::CONVERT_ARG)?.into()),
+                ),
+                App::IMPORT_ARG => (
+                    App::IMPORT_ARG,
+                    Command::Import {
+                        source: value(App::IMPORT_ARG)?.into(),
+                    },
+                ),
+                App::EXPORT_ARG => {
+                    let target = value(App::EXPORT_ARG)?.into();
+                    let mut names = Vec::new();
+                    while let Some(name) = args.next_if(|arg| !arg.starts_with("--")) {
+                        names.push(name);
+                    }
+                    (App::EXPORT_ARG, Command::Export { target, names })
+                }
+                _ => Err(ThisError::UnknownArgument(arg))?,
+            };
+
+            if let Some((other, _)) = command {
+                Err(ThisError::ConflictingArguments(other, name))?;
             }
+            command = Some((name, new_command));
         }
 
-        hkm.event_loop();
+        if on_conflict.is_some() && !matches!(command, Some((App::IMPORT_ARG, _))) {
+            Err(ThisError::ArgumentWithout(
+                App::ON_CONFLICT_ARG,
+                App::IMPORT_ARG,
+            ))?;
+        }
+        Ok(Self {
+            config,
+            command: command.map_or(Command::App, |(_, command)| command),
+            on_conflict,
+        })
+    }
+}
 
-        Ok(())
+fn run_headless() -> Result<()> {
+    // Settings applied through a hotkey can only be kept once confirmed in a dialog
+    confirm::check()?;
+
+    let mut hkm: HotkeyManager<Result<()>> = HotkeyManager::new();
+    let presets = DATA.lock().unwrap().presets.clone();
+    for preset in presets {
+        let p = preset.clone();
+        if let Some(Keybind {
+            trigger_key,
+            modifiers,
+        }) = preset.lock().unwrap().keybind.clone()
+        {
+            hkm.register_hotkey(trigger_key, &modifiers, move || {
+                // Not holding the lock while the dialog waits for an answer
+                let monitors = DATA.lock().unwrap().monitors.clone();
+                let snapshot = Preset::from_current_config(&monitors)?;
+                let report = p.lock().unwrap().apply(&monitors)?;
+                if report.outcome == ApplyOutcome::Applied {
+                    match confirm::keep_settings() {
+                        Ok(true) => Monitor::persist_settings(&monitors)?,
+                        Ok(false) => {
+                            snapshot.apply(&monitors)?;
+                        }
+                        // Settings nobody confirmed are reverted all the same
+                        Err(err) => {
+                            snapshot.apply(&monitors)?;
+                            return Err(err);
+                        }
+                    }
+                }
+                Ok(())
+            })?;
+        }
+    }
+
+    #[cfg(windows)]
+    hkm.event_loop();
+    #[cfg(not(windows))]
+    hkm.event_loop()?;
+
+    Ok(())
+}
+
+/// Prints what applying each preset would change, exits with an error code if any of them can't be applied
+fn run_dry_run() -> Result<()> {
+    let data = DATA.lock().unwrap();
+    let mut can_apply = true;
+    for preset in &data.presets {
+        let preset = preset.lock().unwrap();
+        let dry_run = preset.dry_run(&data.monitors)?;
+        can_apply &= dry_run.error.is_none();
+        println!("Preset \"{}\"\n{}\n", preset, dry_run);
+    }
+
+    if !can_apply {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Prints the problems of the layout of each preset, exits with an error code if any of them has one
+fn run_check() -> Result<()> {
+    let data = DATA.lock().unwrap();
+    let mut valid = true;
+    for preset in &data.presets {
+        let preset = preset.lock().unwrap();
+        println!("Preset \"{}\"", preset);
+        match preset.check(&data.monitors) {
+            Ok(diagnostics) if diagnostics.is_empty() => println!("No problem found"),
+            Ok(diagnostics) => {
+                valid = false;
+                for diagnostic in diagnostics {
+                    println!("{}", diagnostic);
+                }
+            }
+            Err(err) => {
+                valid = false;
+                println!("The layout can't be checked: {}", err);
+            }
+        }
+        println!();
+    }
+
+    if !valid {
+        process::exit(1);
+    }
+    Ok(())
+}
+
+/// Writes the presets to another file, in the format of its extension
+fn run_convert(config_path: &Path, target: &Path) -> Result<()> {
+    config::convert(config_path, target)?;
+    println!(
+        "Presets of {} written to {}, use them with {} {}",
+        config_path.display(),
+        target.display(),
+        App::CONFIG_ARG,
+        target.display()
+    );
+    Ok(())
+}
+
+/// Writes the presets with the given names, every preset if none is given, to a standalone file
+fn run_export(target: &Path, names: &[String]) -> Result<()> {
+    let data = DATA.lock().unwrap();
+    let presets: Vec<Preset> = if names.is_empty() {
+        data.presets
+            .iter()
+            .map(|preset| preset.lock().unwrap().clone())
+            .collect()
     } else {
-        color_eyre::install()?;
-        ratatui::run(|terminal| App::default().run(terminal))?;
-        Ok(())
+        names
+            .iter()
+            .map(|name| {
+                data.presets
+                    .iter()
+                    .map(|preset| preset.lock().unwrap())
+                    .find(|preset| preset.name == *name)
+                    .map(|preset| preset.clone())
+                    .ok_or(ThisError::PresetNotFound(name.to_string()))
+            })
+            .collect::<std::result::Result<_, _>>()?
+    };
+
+    let overwrite = fs::exists(target)?
+        && confirm::ask(&format!(
+            "{} exists already, overwrite it?",
+            target.display()
+        ))?;
+    config::export(target, &presets, overwrite)?;
+    println!(
+        "{} exported to {}",
+        presets
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<String>>()
+            .join(", "),
+        target.display()
+    );
+    Ok(())
+}
+
+/// Adds the presets of a file, refusing to when some are named like existing ones and no [`OnConflict`] is given
+fn run_import(source: &Path, on_conflict: Option<OnConflict>) -> Result<()> {
+    let imported = config::import(source)?;
+    let mut data = DATA.lock().unwrap();
+    let conflicts = transfer::conflicts(&data.presets, &imported);
+    let on_conflict = match on_conflict {
+        Some(on_conflict) => on_conflict,
+        None if conflicts.is_empty() => OnConflict::Skip,
+        None => Err(ThisError::ImportConflicts(conflicts))?,
+    };
+
+    let report = transfer::import(&mut data.presets, imported, on_conflict);
+    data.save()?;
+    println!("{}", report);
+    Ok(())
+}
+
+/// Offers to restore the newest backup of the presets when they can't be read, returns the error otherwise
+fn restore_presets(config_path: &Path, err: Report) -> Result<Vec<Preset>> {
+    let Some(backup) = config::newest_valid_backup(config_path)? else {
+        return Err(err);
+    };
+
+    let question = format!(
+        "The presets can't be read: {}\nRestore the backup {}?",
+        err,
+        backup.display()
+    );
+    if confirm::ask(&question)? {
+        config::restore(config_path, &backup)
+    } else {
+        Err(err)
     }
 }
 
@@ -75,18 +377,81 @@ fn kill_others(sys: &mut System) {
     }
 }
 
-pub fn author_path() -> PathBuf {
-    dirs::config_dir().unwrap().join("Adrien5902")
-}
-
 #[derive(Default, Debug)]
 pub struct Data {
     pub monitors: Vec<Monitor>,
     pub presets: Vec<Arc<Mutex<Preset>>>,
+    /// Presets file, see [`config::path`]
+    pub config_path: PathBuf,
 }
 
 impl Data {
     fn save(&self) -> Result<()> {
-        Preset::write(&self.presets)
+        config::write(&self.config_path, &self.presets)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Result<Args> {
+        Args::parse(args.iter().map(|arg| arg.to_string()))
+    }
+
+    fn error(args: &[&str]) -> String {
+        parse(args).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn parses_commands() {
+        assert_eq!(parse(&[]).unwrap().command, Command::App);
+        let args = parse(&["--config", "presets.toml", "--headless"]).unwrap();
+        assert_eq!(args.config.as_deref(), Some("presets.toml"));
+        assert_eq!(args.command, Command::Headless);
+        assert_eq!(
+            parse(&[
+                "--export",
+                "out.json",
+                "Desk",
+                "Sofa",
+                "--config",
+                "presets.toml"
+            ])
+            .unwrap()
+            .command,
+            Command::Export {
+                target: PathBuf::from("out.json"),
+                names: vec![String::from("Desk"), String::from("Sofa")],
+            }
+        );
+        let args = parse(&["--on-conflict", "skip", "--import", "in.json"]).unwrap();
+        assert_eq!(
+            args.command,
+            Command::Import {
+                source: PathBuf::from("in.json")
+            }
+        );
+        assert_eq!(args.on_conflict, Some(OnConflict::Skip));
+    }
+
+    #[test]
+    fn refuses_unknown_and_conflicting_arguments() {
+        assert_eq!(error(&["--config", "--headless"]), "--config needs a value");
+        assert_eq!(error(&["--export"]), "--export needs a value");
+        assert_eq!(error(&["--chek"]), "Unknown argument --chek");
+        assert_eq!(error(&["Desk"]), "Unknown argument Desk");
+        assert_eq!(
+            error(&["--convert", "out.toml", "--dry-run"]),
+            "--convert and --dry-run can't be used together"
+        );
+        assert_eq!(
+            error(&["--import", "in.json", "--check"]),
+            "--import and --check can't be used together"
+        );
+        assert_eq!(
+            error(&["--export", "out.json", "--on-conflict", "skip"]),
+            "--on-conflict only goes along with --import"
+        );
     }
 }
@@ -1,30 +1,39 @@
-use crate::{display_settings::DisplaySettings, error::ThisError};
+use crate::{
+    backend::{DisplayBackend, MonitorDevice},
+    display_settings::{DisplayMode, DisplaySettings},
+    edid::Edid,
+};
 use color_eyre::eyre::Result;
 use serde::Serialize;
-use std::hash::Hash;
-use windows::{
-    Win32::{
-        Graphics::Gdi::{
-            CDS_UPDATEREGISTRY, ChangeDisplaySettingsExW, DEVMODEW, DISP_CHANGE_SUCCESSFUL,
-            DISPLAY_DEVICEW, DM_DISPLAYORIENTATION, DM_PELSHEIGHT, DM_PELSWIDTH, DM_POSITION,
-            EDS_ROTATEDMODE, ENUM_CURRENT_SETTINGS, EnumDisplayDevicesW, EnumDisplaySettingsExW,
-        },
-        UI::WindowsAndMessaging::EDD_GET_DEVICE_INTERFACE_NAME,
-    },
-    core::PCWSTR,
-};
+use std::{fmt::Display, hash::Hash, sync::Arc};
 
 pub type MonitorName = String;
 
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Clone)]
 pub struct Monitor {
-    #[serde(flatten)]
     pub name: MonitorName,
 
+    /// Backend specific handle of the monitor
+    #[serde(skip)]
+    pub id: String,
+    /// Name the monitor went by before it had a stable one
+    #[serde(skip)]
+    pub legacy_name: Option<MonitorName>,
+    /// Make, model and size of the monitor, when the backend can read its EDID
     #[serde(skip)]
-    pub monitor: DISPLAY_DEVICEW,
+    pub edid: Option<Edid>,
     #[serde(skip)]
-    pub adapter: DISPLAY_DEVICEW,
+    backend: Arc<dyn DisplayBackend>,
+}
+
+impl Display for Monitor {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if let Some(edid) = &self.edid {
+            f.write_str(&format!(" ({})", edid))?;
+        }
+        Ok(())
+    }
 }
 
 impl Eq for Monitor {}
@@ -40,125 +49,76 @@ impl Hash for Monitor {
 }
 
 impl Monitor {
-    pub fn get_name(device: &DISPLAY_DEVICEW) -> MonitorName {
-        wide_to_string(&device.DeviceName)
+    pub fn new(device: MonitorDevice, backend: Arc<dyn DisplayBackend>) -> Self {
+        Self {
+            name: device.name,
+            id: device.id,
+            legacy_name: device.legacy_name,
+            edid: device.edid,
+            backend,
+        }
     }
 
-    pub fn get_monitors() -> Vec<Monitor> {
-        let mut monitors = Vec::new();
-        unsafe {
-            let mut device_index = 0;
-
-            loop {
-                let mut adapter = DISPLAY_DEVICEW::default();
-                adapter.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
-
-                let success = EnumDisplayDevicesW(
-                    PCWSTR::null(),
-                    device_index,
-                    &mut adapter,
-                    EDD_GET_DEVICE_INTERFACE_NAME,
-                );
-
-                if !success.as_bool() {
-                    break;
-                }
-
-                let mut monitor_index = 0;
-                loop {
-                    let mut monitor = DISPLAY_DEVICEW::default();
-                    monitor.cb = std::mem::size_of::<DISPLAY_DEVICEW>() as u32;
-
-                    let success = EnumDisplayDevicesW(
-                        PCWSTR(adapter.DeviceName.as_ptr()),
-                        monitor_index,
-                        &mut monitor,
-                        0,
-                    );
-
-                    if !success.as_bool() {
-                        break;
-                    }
-
-                    monitor_index += 1;
-
-                    monitors.push(Monitor {
-                        monitor,
-                        adapter,
-                        name: Self::get_name(&monitor),
-                    });
-                }
-
-                device_index += 1;
+    pub fn get_monitors(backend: &Arc<dyn DisplayBackend>) -> Result<Vec<Monitor>> {
+        let mut devices = backend.enumerate()?;
+
+        // Identical monitors without a serial can't be told apart, they keep the name the backend gives them
+        let names: Vec<MonitorName> = devices.iter().map(|device| device.name.clone()).collect();
+        for device in &mut devices {
+            if names.iter().filter(|name| **name == device.name).count() > 1
+                && let Some(legacy_name) = device.legacy_name.take()
+            {
+                device.name = legacy_name;
             }
         }
+
+        let mut monitors: Vec<Monitor> = devices
+            .into_iter()
+            .map(|device| Self::new(device, backend.clone()))
+            .collect();
         monitors.sort_by(|a, b| a.name.cmp(&b.name));
+        Ok(monitors)
+    }
+
+    /// Makes the settings applied last survive a restart once the user kept them, see [`DisplayBackend::persist`]
+    pub fn persist_settings(monitors: &[Monitor]) -> Result<()> {
         monitors
+            .first()
+            .map_or(Ok(()), |monitor| monitor.backend.persist())
     }
 
-    pub fn get_lpszdevicename(&self) -> PCWSTR {
-        PCWSTR(self.adapter.DeviceName.as_ptr())
+    pub fn backend(&self) -> &Arc<dyn DisplayBackend> {
+        &self.backend
     }
 
     pub fn get_display_settings(&self) -> Result<DisplaySettings> {
-        let dm = self.get_devmodew()?;
-        Ok(dm.into())
+        self.backend.get_display_settings(self)
     }
 
-    pub fn get_devmodew(&self) -> Result<DEVMODEW> {
-        let mut dm = DEVMODEW::default();
-        dm.dmSize = std::mem::size_of::<DEVMODEW>() as u16;
-
-        unsafe {
-            if !EnumDisplaySettingsExW(
-                self.get_lpszdevicename(),
-                ENUM_CURRENT_SETTINGS,
-                &mut dm,
-                EDS_ROTATEDMODE,
-            )
-            .as_bool()
-            {
-                Err(ThisError::EnumDisplaySettings)?;
-            }
+    /// Lists every mode the monitor supports, sorted by resolution then refresh rate
+    pub fn get_modes(&self) -> Result<Vec<DisplayMode>> {
+        let mut modes = self.backend.get_modes(self)?;
+        modes.sort_by_key(|mode| (mode.resolution, mode.refresh_rate, mode.bits_per_pixel));
+        modes.dedup();
+        Ok(modes)
+    }
+
+    /// Whether the resolution and refresh rate of the settings are supported by the monitor
+    pub fn supports(&self, display_settings: &DisplaySettings) -> Result<bool> {
+        if !display_settings.enabled
+            || display_settings.resolution.is_none() && display_settings.refresh_rate.is_none()
+        {
+            return Ok(true);
         }
 
-        Ok(dm)
+        let current = self.get_display_settings()?;
+        Ok(self
+            .get_modes()?
+            .iter()
+            .any(|mode| mode.matches(display_settings, &current)))
     }
 
     pub fn set_settings(&self, display_settings: &DisplaySettings) -> Result<()> {
-        unsafe {
-            let mut dm = self.get_devmodew()?;
-
-            if !DisplaySettings::from(dm)
-                .orientation
-                .is_same_direction(&display_settings.orientation)
-            {
-                std::mem::swap(&mut dm.dmPelsWidth, &mut dm.dmPelsHeight);
-            }
-
-            dm.Anonymous1.Anonymous2.dmDisplayOrientation = display_settings.orientation.into();
-            dm.Anonymous1.Anonymous2.dmPosition = display_settings.position.into();
-
-            dm.dmFields |= DM_DISPLAYORIENTATION | DM_PELSWIDTH | DM_PELSHEIGHT | DM_POSITION;
-
-            let result = ChangeDisplaySettingsExW(
-                self.get_lpszdevicename(),
-                Some(&dm),
-                None,
-                CDS_UPDATEREGISTRY,
-                None,
-            );
-
-            if result == DISP_CHANGE_SUCCESSFUL {
-                Ok(())
-            } else {
-                Err(ThisError::DispChange(result))?
-            }
-        }
+        self.backend.apply(&[(self, display_settings)])
     }
 }
-
-fn wide_to_string(wide: &[u16]) -> String {
-    let len = wide.iter().position(|&c| c == 0).unwrap_or(wide.len());
-    String::from_utf16_lossy(&wide[..len])
-}
@@ -1,36 +1,137 @@
 use crate::{
-    author_path,
-    display_settings::DisplaySettings,
+    backend::Origin,
+    display_settings::{Align, DisplaySettings, Placement, Pos, Resolution, Side},
     error::ThisError,
+    keys::VKey,
+    layout::{self, Diagnostic, Rect},
     monitor::{Monitor, MonitorName},
 };
-use color_eyre::eyre::Result;
+use color_eyre::eyre::{Report, Result};
 use serde::{Deserialize, Serialize, de::Visitor};
 use std::{
-    collections::HashMap,
+    collections::{BTreeSet, HashMap},
     fmt::Display,
-    fs,
-    path::PathBuf,
-    sync::{Arc, Mutex},
 };
-use win_hotkeys::VKey;
 
 #[derive(Debug, Serialize, Deserialize, Default, Clone)]
 pub struct Preset {
+    /// Shown everywhere the preset is, unique among the presets
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
     pub keybind: Option<Keybind>,
     map: HashMap<MonitorName, DisplaySettings>,
+    #[serde(default, skip_serializing_if = "MissingMonitorPolicy::is_strict")]
+    pub on_missing_monitor: MissingMonitorPolicy,
+}
+
+/// What applying a preset does when some of its monitors are not connected
+#[derive(Debug, Serialize, Deserialize, Default, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "kebab-case")]
+pub enum MissingMonitorPolicy {
+    /// The preset can't be applied
+    #[default]
+    Strict,
+    /// The connected monitors are applied where the preset puts them, the missing ones are skipped
+    ApplyAvailable,
+    /// The connected monitors are applied and moved to close the gaps left by the missing ones
+    #[serde(rename = "re-layout")]
+    Relayout,
+}
+
+impl MissingMonitorPolicy {
+    fn is_strict(&self) -> bool {
+        *self == Self::Strict
+    }
+
+    /// Policy following this one, to cycle through them
+    pub fn next(self) -> Self {
+        match self {
+            Self::Strict => Self::ApplyAvailable,
+            Self::ApplyAvailable => Self::Relayout,
+            Self::Relayout => Self::Strict,
+        }
+    }
+}
+
+impl Display for MissingMonitorPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Strict => "strict",
+            Self::ApplyAvailable => "apply available",
+            Self::Relayout => "re-layout",
+        })
+    }
 }
 
 impl Display for Preset {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.write_str(
-            &self
-                .map
+        f.write_str(&self.name)
+    }
+}
+
+/// Outcome of [`Preset::apply`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApplyOutcome {
+    Applied,
+    /// Every monitor was already in its target state, nothing was changed
+    AlreadyApplied,
+}
+
+/// What [`Preset::apply`] did
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ApplyReport {
+    pub outcome: ApplyOutcome,
+    /// Monitors of the preset left out because they are not connected, see [`MissingMonitorPolicy`]
+    pub skipped: Vec<MonitorName>,
+    /// Problems of the layout applied, see [`Preset::check`]
+    pub diagnostics: Vec<Diagnostic>,
+}
+
+/// What applying a preset would do, see [`Preset::dry_run`]
+#[derive(Debug)]
+pub struct DryRun {
+    /// Name, current and target settings of every monitor of the preset, as the backend sees them
+    pub changes: Vec<(MonitorName, DisplaySettings, DisplaySettings)>,
+    /// How far the backend moves the layout of the preset, see [`Origin`]
+    pub offset: Pos,
+    /// Monitors of the preset left out because they are not connected
+    pub skipped: Vec<MonitorName>,
+    /// Why the preset can't be applied
+    pub error: Option<Report>,
+}
+
+impl Display for DryRun {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.offset != Pos::default() {
+            writeln!(
+                f,
+                "Positions as the backend applies them, the layout moved by {}/{} for its top left corner to sit at 0,0",
+                self.offset.x, self.offset.y
+            )?;
+        }
+        for (name, current, target) in &self.changes {
+            if target.differs_from(current) {
+                writeln!(f, "{}: {} -> {}", name, current, target)?;
+            } else {
+                writeln!(f, "{}: {} (unchanged)", name, current)?;
+            }
+        }
+        for name in &self.skipped {
+            writeln!(f, "{}: not connected, skipped", name)?;
+        }
+
+        match &self.error {
+            Some(err) => write!(f, "The preset can't be applied: {}", err),
+            None if self
+                .changes
                 .iter()
-                .map(|(monitor_name, settings)| format!("{},{}", monitor_name.clone(), settings))
-                .collect::<Vec<String>>()
-                .join(" "),
-        )
+                .all(|(_, current, target)| !target.differs_from(current)) =>
+            {
+                f.write_str("The preset is already applied")
+            }
+            None => f.write_str("The preset can be applied"),
+        }
     }
 }
 
@@ -53,6 +154,23 @@ impl Display for Keybind {
     }
 }
 
+/// Same keys, whatever the order of the modifiers
+impl PartialEq for Keybind {
+    fn eq(&self, other: &Self) -> bool {
+        let keys = |keybind: &Self| {
+            (
+                keybind.trigger_key.to_string(),
+                keybind
+                    .modifiers
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<BTreeSet<_>>(),
+            )
+        };
+        keys(self) == keys(other)
+    }
+}
+
 impl Serialize for Keybind {
     fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
     where
@@ -108,60 +226,1243 @@ impl TryFrom<&str> for Keybind {
 }
 
 impl Preset {
-    fn get_path() -> PathBuf {
-        author_path().join("monitors_config.json")
+    pub fn from_current_config(monitors: &[Monitor]) -> Result<Self> {
+        let mut map: Vec<(MonitorName, DisplaySettings)> = monitors
+            .iter()
+            .map(|monitor| Ok((monitor.name.clone(), monitor.get_display_settings()?)))
+            .collect::<Result<_>>()?;
+
+        // Monitors showing the same area of the desktop mirror the first of them
+        for i in 1..map.len() {
+            let (before, after) = map.split_at_mut(i);
+            let settings = &mut after[0].1;
+            if let Some((leader, _)) = before
+                .iter()
+                .find(|(_, other)| other.mirror.is_none() && other.covers_same_area(settings))
+            {
+                settings.mirror = Some(leader.clone());
+                settings.primary = false;
+            }
+        }
+
+        Ok(Self {
+            name: String::new(),
+            description: None,
+            keybind: None,
+            map: map.into_iter().collect(),
+            on_missing_monitor: MissingMonitorPolicy::default(),
+        })
     }
 
-    pub fn write(list: &[Arc<Mutex<Self>>]) -> Result<()> {
-        fs::create_dir_all(author_path())?;
-        fs::write(Self::get_path(), serde_json::to_string(list)?)?;
-        Ok(())
+    /// First of `Preset 1`, `Preset 2`... not taken by another preset
+    pub fn default_name<'a>(taken: impl Iterator<Item = &'a str> + Clone) -> String {
+        (1..)
+            .map(|index| format!("Preset {}", index))
+            .find(|name| !taken.clone().any(|taken| taken == name))
+            .unwrap()
+    }
+
+    /// Settings of every monitor of the preset, sorted by monitor name
+    pub fn layout(&self) -> String {
+        let mut map: Vec<_> = self.map.iter().collect();
+        map.sort_by_key(|(monitor_name, _)| *monitor_name);
+        map.into_iter()
+            .map(|(monitor_name, settings)| format!("{}: {}", monitor_name, settings))
+            .collect::<Vec<String>>()
+            .join("\n")
     }
 
-    pub fn read() -> Result<Vec<Self>> {
-        let path = Self::get_path();
-        if !fs::exists(&path)? {
-            return Ok(Vec::new());
+    /// Renames the monitors keyed on their legacy name, returns whether anything was renamed
+    pub fn migrate_names(&mut self, monitors: &[Monitor]) -> bool {
+        let mut migrated = false;
+        for monitor in monitors {
+            let Some(legacy_name) = monitor
+                .legacy_name
+                .as_ref()
+                .filter(|legacy_name| **legacy_name != monitor.name)
+            else {
+                continue;
+            };
+
+            if let Some(settings) = self.map.remove(legacy_name) {
+                self.map.entry(monitor.name.clone()).or_insert(settings);
+                migrated = true;
+            }
+            for settings in self.map.values_mut() {
+                if settings.mirror.as_ref() == Some(legacy_name) {
+                    settings.mirror = Some(monitor.name.clone());
+                    migrated = true;
+                }
+                if let Some(placement) = &mut settings.placement
+                    && placement.side.reference() == legacy_name
+                {
+                    *placement.side.reference_mut() = monitor.name.clone();
+                    migrated = true;
+                }
+            }
         }
 
-        let s = fs::read_to_string(&path)?;
-        let value = serde_json::from_str(&s)?;
-        Ok(value)
+        migrated
     }
 
-    pub fn from_current_config(monitors: &[Monitor]) -> Result<Self> {
-        Ok(Self {
-            keybind: None,
-            map: monitors
-                .iter()
-                .map(|monitor| Ok((monitor.name.clone(), monitor.get_display_settings()?)))
-                .collect::<Result<_>>()?,
-        })
+    /// Monitors of the preset meant to be turned on that are not connected
+    pub fn missing_monitors(&self, monitors: &[Monitor]) -> Vec<MonitorName> {
+        let mut missing: Vec<MonitorName> = self
+            .map
+            .iter()
+            .filter(|(name, settings)| {
+                settings.enabled && !monitors.iter().any(|monitor| monitor.name == **name)
+            })
+            .map(|(name, _)| name.clone())
+            .collect();
+        missing.sort();
+        missing
     }
 
+    /// Binds the settings of the preset to the connected monitors, missing ones being an error when the preset is strict
     pub fn get_monitor_map<'a, 'b>(
         &'a self,
         monitors: &'b [Monitor],
     ) -> Result<HashMap<&'b Monitor, &'a DisplaySettings>> {
-        Ok(self
+        self.map
+            .iter()
+            .filter_map(|(name, settings)| {
+                match monitors.iter().find(|m| m.name == *name) {
+                    Some(monitor) => Some(Ok((monitor, settings))),
+                    // A monitor meant to be turned off may as well be unplugged
+                    None if !settings.enabled => None,
+                    None if !self.on_missing_monitor.is_strict() => None,
+                    None => Some(Err(ThisError::MonitorNotFound.into())),
+                }
+            })
+            .collect::<Result<HashMap<_, _>>>()
+    }
+
+    /// Applies every monitor at once, if anything fails the previous settings of every monitor are restored
+    ///
+    /// Monitors already in their target state are left alone to avoid needless mode sets
+    pub fn apply(&self, monitors: &[Monitor]) -> Result<ApplyReport> {
+        let snapshot = Self::from_current_config(monitors)?;
+        let settings = self.prepare(monitors)?;
+        let skipped = self.missing_monitors(monitors);
+        let diagnostics = layout::check(&Self::rects(&settings)?);
+
+        let mut changed = Vec::new();
+        for (monitor, target) in &settings {
+            changed.push(target.differs_from(&monitor.get_display_settings()?));
+        }
+        if !changed.contains(&true) {
+            return Ok(ApplyReport {
+                outcome: ApplyOutcome::AlreadyApplied,
+                skipped,
+                diagnostics,
+            });
+        }
+
+        // Mirrors are set up from their leader, a whole group is applied when one of its monitors changes
+        let group = |(monitor, settings): &(&Monitor, DisplaySettings)| {
+            settings.mirror.clone().unwrap_or(monitor.name.clone())
+        };
+        let changed_groups: Vec<MonitorName> = settings
+            .iter()
+            .zip(&changed)
+            .filter(|(_, changed)| **changed)
+            .map(|(settings, _)| group(settings))
+            .collect();
+        let settings: Vec<_> = settings
+            .into_iter()
+            .zip(changed)
+            .filter(|(settings, changed)| *changed || changed_groups.contains(&group(settings)))
+            .map(|(settings, _)| settings)
+            .collect();
+
+        if let Err(err) = Self::apply_settings(&settings) {
+            let rollback = snapshot
+                .prepare(monitors)
+                .and_then(|settings| Self::apply_settings(&settings));
+
+            Err(match rollback {
+                Ok(()) => ThisError::RolledBack(err),
+                Err(rollback_err) => ThisError::RollbackFailed(err, rollback_err),
+            })?;
+        }
+
+        Ok(ApplyReport {
+            outcome: ApplyOutcome::Applied,
+            skipped,
+            diagnostics,
+        })
+    }
+
+    /// Looks for overlapping, unreachable or misplaced monitors in the layout of the preset,
+    /// which some backends refuse or silently change into another layout
+    pub fn check(&self, monitors: &[Monitor]) -> Result<Vec<Diagnostic>> {
+        let settings = self.resolve(monitors)?;
+        Ok(layout::check(&Self::rects(&settings)?))
+    }
+
+    /// Snaps, de-overlaps and re-origins the positions of the preset, see [`layout::normalize`], returns whether anything moved
+    ///
+    /// Monitors placed next to another one follow it and are left out, as are the ones with an unknown resolution
+    pub fn normalize(&mut self, monitors: &[Monitor]) -> Result<bool> {
+        let mut rects = Vec::new();
+        for (name, settings) in &self.map {
+            if !settings.enabled || settings.mirror.is_some() || settings.placement.is_some() {
+                continue;
+            }
+            let size = match monitors.iter().find(|monitor| &monitor.name == name) {
+                Some(monitor) => Self::size(monitor, settings)?,
+                None => settings.resolution.map(|resolution| {
+                    resolution
+                        .oriented(settings.orientation)
+                        .scaled(settings.scale)
+                }),
+            };
+            if let Some(size) = size {
+                rects.push(Rect {
+                    name: name.clone(),
+                    position: settings.position,
+                    size,
+                });
+            }
+        }
+        rects.sort_by(|a, b| a.name.cmp(&b.name));
+
+        let primary = self
             .map
             .iter()
-            .map(|(name, settings)| {
-                let monitor = monitors
+            .find(|(_, settings)| settings.enabled && settings.primary)
+            .map(|(name, _)| name.clone());
+        let origin = layout::normalize(&mut rects, primary.as_ref());
+
+        let mut moved = false;
+        for (name, settings) in &mut self.map {
+            let leader = settings.mirror.as_ref().unwrap_or(name);
+            let position = match rects.iter().find(|rect| &rect.name == leader) {
+                Some(rect) => rect.position,
+                None => Pos {
+                    x: settings.position.x - origin.x,
+                    y: settings.position.y - origin.y,
+                },
+            };
+            moved |= settings.position != position;
+            settings.position = position;
+        }
+        Ok(moved)
+    }
+
+    /// Whether every monitor of the preset is already in its target state
+    pub fn is_applied(&self, monitors: &[Monitor]) -> Result<bool> {
+        let mut settings = self.resolve(monitors)?;
+        Self::to_backend(&mut settings);
+        for (monitor, target) in &settings {
+            if target.differs_from(&monitor.get_display_settings()?) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    }
+
+    /// Goes through applying the preset without changing anything, the backend only tests the settings
+    pub fn dry_run(&self, monitors: &[Monitor]) -> Result<DryRun> {
+        let mut settings = match self.resolve(monitors) {
+            Ok(settings) => settings,
+            Err(err) => {
+                return Ok(DryRun {
+                    changes: Vec::new(),
+                    offset: Pos::default(),
+                    skipped: Vec::new(),
+                    error: Some(err),
+                });
+            }
+        };
+        let offset = Self::to_backend(&mut settings);
+
+        let mut changes = Vec::new();
+        for (monitor, target) in &settings {
+            changes.push((
+                monitor.name.clone(),
+                monitor.get_display_settings()?,
+                target.clone(),
+            ));
+        }
+        changes.sort_by(|(a, ..), (b, ..)| a.cmp(b));
+
+        let error = Self::check_modes(&settings)
+            .and_then(|()| Self::test_settings(&settings))
+            .err();
+
+        Ok(DryRun {
+            changes,
+            offset,
+            skipped: self.missing_monitors(monitors),
+            error,
+        })
+    }
+
+    /// Settings of every monitor of the preset as the backend will apply them, checked against the modes of the monitors
+    fn prepare<'a>(&self, monitors: &'a [Monitor]) -> Result<Vec<(&'a Monitor, DisplaySettings)>> {
+        let mut settings = self.resolve(monitors)?;
+        Self::check_modes(&settings)?;
+        Self::to_backend(&mut settings);
+        Ok(settings)
+    }
+
+    /// Moves the resolved settings where the backend puts them, see [`Origin`], returns by how much
+    ///
+    /// Current settings are read in the backend's coordinates, targets are compared to them once moved
+    fn to_backend(settings: &mut [(&Monitor, DisplaySettings)]) -> Pos {
+        let Some((monitor, _)) = settings.first() else {
+            return Pos::default();
+        };
+        let offset = match monitor.backend().origin() {
+            Origin::Primary => return Pos::default(),
+            Origin::TopLeft => {
+                let positions: Vec<Pos> = settings
                     .iter()
-                    .find(|m| m.name == *name)
-                    .ok_or(ThisError::MonitorNotFound)?;
+                    .filter(|(_, settings)| settings.enabled)
+                    .map(|(_, settings)| settings.position)
+                    .collect();
+                Pos {
+                    x: -positions.iter().map(|pos| pos.x).min().unwrap_or_default(),
+                    y: -positions.iter().map(|pos| pos.y).min().unwrap_or_default(),
+                }
+            }
+        };
 
-                Ok((monitor, settings))
-            })
-            .collect::<Result<HashMap<_, _>>>()?)
+        for (_, settings) in settings.iter_mut() {
+            settings.position.x += offset.x;
+            settings.position.y += offset.y;
+        }
+        offset
+    }
+
+    /// Settings of every monitor of the preset with their mirrors and placements resolved, and moved so the primary monitor sits at 0,0
+    fn resolve<'a>(&self, monitors: &'a [Monitor]) -> Result<Vec<(&'a Monitor, DisplaySettings)>> {
+        let mut settings: Vec<(&Monitor, DisplaySettings)> = self
+            .get_monitor_map(monitors)?
+            .into_iter()
+            .map(|(monitor, settings)| (monitor, settings.clone()))
+            .collect();
+        settings.sort_by(|(a, _), (b, _)| a.name.cmp(&b.name));
+
+        let missing = self.missing_monitors(monitors);
+        Self::replace_missing(&mut settings, &missing);
+        Self::resolve_mirrors(&mut settings)?;
+        Self::place(&mut settings)?;
+
+        if !missing.is_empty() {
+            // The primary monitor is missing, the top left one takes over
+            if missing.iter().any(|name| self.map[name].primary)
+                && let Some((_, settings)) = settings
+                    .iter_mut()
+                    .filter(|(_, settings)| settings.enabled && settings.mirror.is_none())
+                    .min_by_key(|(_, settings)| (settings.position.y, settings.position.x))
+            {
+                settings.primary = true;
+            }
+            if self.on_missing_monitor == MissingMonitorPolicy::Relayout {
+                Self::close_gaps(&mut settings)?;
+            }
+        }
+
+        // The primary monitor has to sit at 0,0, the whole layout is moved along with it
+        if let Some(origin) = settings
+            .iter()
+            .find(|(_, settings)| settings.enabled && settings.primary)
+            .map(|(_, settings)| settings.position)
+        {
+            for (_, settings) in &mut settings {
+                settings.position.x -= origin.x;
+                settings.position.y -= origin.y;
+            }
+        }
+
+        Ok(settings)
+    }
+
+    fn check_modes(settings: &[(&Monitor, DisplaySettings)]) -> Result<()> {
+        let mut unsupported = Vec::new();
+        for (monitor, settings) in settings {
+            if !monitor.supports(settings)? {
+                unsupported.push(format!("{} ({})", monitor.name, settings));
+            }
+        }
+        if !unsupported.is_empty() {
+            Err(ThisError::UnsupportedModes(unsupported))?;
+        }
+
+        Ok(())
+    }
+
+    fn apply_settings(settings: &[(&Monitor, DisplaySettings)]) -> Result<()> {
+        let settings: Vec<_> = settings
+            .iter()
+            .map(|(monitor, settings)| (*monitor, settings))
+            .collect();
+        if let Some((monitor, _)) = settings.first() {
+            monitor.backend().apply(&settings)?
+        }
+
+        Ok(())
+    }
+
+    fn test_settings(settings: &[(&Monitor, DisplaySettings)]) -> Result<()> {
+        let settings: Vec<_> = settings
+            .iter()
+            .map(|(monitor, settings)| (*monitor, settings))
+            .collect();
+        if let Some((monitor, _)) = settings.first() {
+            monitor.backend().test(&settings)?
+        }
+
+        Ok(())
+    }
+
+    /// The first monitor mirroring a missing one leads the group in its place
+    fn replace_missing(settings: &mut [(&Monitor, DisplaySettings)], missing: &[MonitorName]) {
+        for leader in missing {
+            let mut new_leader: Option<MonitorName> = None;
+            for (monitor, settings) in settings.iter_mut() {
+                if settings.mirror.as_ref() == Some(leader) {
+                    settings.mirror = new_leader.clone();
+                    new_leader.get_or_insert(monitor.name.clone());
+                }
+            }
+        }
+    }
+
+    /// Moves the monitors so no empty column or row is left between them, like the ones left by missing monitors
+    fn close_gaps(settings: &mut [(&Monitor, DisplaySettings)]) -> Result<()> {
+        let mut columns = Vec::new();
+        let mut rows = Vec::new();
+        for (monitor, settings) in settings.iter() {
+            if !settings.enabled {
+                continue;
+            }
+
+            if let Some(size) = Self::size(monitor, settings)? {
+                columns.push((settings.position.x, settings.position.x + size.width as i32));
+                rows.push((
+                    settings.position.y,
+                    settings.position.y + size.height as i32,
+                ));
+            }
+        }
+
+        let (columns, rows) = (gaps(columns), gaps(rows));
+        for (_, settings) in settings.iter_mut() {
+            settings.position.x -= gap_before(&columns, settings.position.x);
+            settings.position.y -= gap_before(&rows, settings.position.y);
+        }
+
+        Ok(())
     }
 
-    pub fn apply(&self, monitors: &[Monitor]) -> Result<()> {
-        for (monitor, settings) in self.get_monitor_map(monitors)?.into_iter() {
-            monitor.set_settings(settings)?
+    /// Turns the placements of the monitors into positions, starting from the monitors with a position
+    /// and placing a monitor once the one it is relative to has its own
+    fn place(settings: &mut [(&Monitor, DisplaySettings)]) -> Result<()> {
+        // Mirrors and turned off monitors don't need a position of their own
+        for (_, settings) in settings.iter_mut() {
+            if !settings.enabled || settings.mirror.is_some() {
+                settings.placement = None;
+            }
+        }
+
+        loop {
+            let mut placed_any = false;
+            let mut pending = Vec::new();
+
+            for index in 0..settings.len() {
+                let (monitor, own) = &settings[index];
+                let Some(placement) = own.placement.clone() else {
+                    continue;
+                };
+
+                let reference = settings
+                    .iter()
+                    .find(|(monitor, settings)| {
+                        &monitor.name == placement.side.reference() && settings.enabled
+                    })
+                    .map(|(monitor, settings)| (*monitor, settings))
+                    .ok_or_else(|| {
+                        ThisError::InvalidPlacement(format!(
+                            "{} is placed {} which isn't connected or turned on",
+                            monitor.name, placement.side
+                        ))
+                    })?;
+                // A mirror sits where its leader does
+                let reference = match &reference.1.mirror {
+                    Some(leader) => settings
+                        .iter()
+                        .find(|(monitor, _)| &monitor.name == leader)
+                        .map(|(monitor, settings)| (*monitor, settings))
+                        .unwrap_or(reference),
+                    None => reference,
+                };
+                if reference.1.placement.is_some() {
+                    pending.push(format!("{} {}", monitor.name, placement.side));
+                    continue;
+                }
+
+                let size = Self::size(monitor, own)?;
+                let reference_size = Self::size(reference.0, reference.1)?;
+                let (Some(size), Some(reference_size)) = (size, reference_size) else {
+                    Err(ThisError::InvalidPlacement(format!(
+                        "the resolution of {} or {} is unknown",
+                        monitor.name, reference.0.name
+                    )))?
+                };
+                let position = placed(&placement, size, reference.1.position, reference_size)
+                    .ok_or_else(|| {
+                        ThisError::InvalidPlacement(format!(
+                            "{} can't be {}",
+                            monitor.name, placement
+                        ))
+                    })?;
+
+                let name = monitor.name.clone();
+                for (monitor, settings) in settings.iter_mut() {
+                    if monitor.name == name || settings.mirror.as_ref() == Some(&name) {
+                        settings.position = position;
+                    }
+                }
+                settings[index].1.placement = None;
+                placed_any = true;
+            }
+
+            if pending.is_empty() {
+                return Ok(());
+            }
+            if !placed_any {
+                Err(ThisError::InvalidPlacement(format!(
+                    "placements going round in circles: {}",
+                    pending.join(", ")
+                )))?;
+            }
+        }
+    }
+
+    /// Areas covered by the monitors turned on, a group of mirrors covering a single one
+    fn rects(settings: &[(&Monitor, DisplaySettings)]) -> Result<Vec<Rect>> {
+        let mut rects = Vec::new();
+        for (monitor, settings) in settings {
+            if !settings.enabled || settings.mirror.is_some() {
+                continue;
+            }
+            if let Some(size) = Self::size(monitor, settings)? {
+                rects.push(Rect {
+                    name: monitor.name.clone(),
+                    position: settings.position,
+                    size,
+                });
+            }
+        }
+        Ok(rects)
+    }
+
+    /// Area covered by a monitor, with its current resolution and scale when the settings don't change them
+    fn size(monitor: &Monitor, settings: &DisplaySettings) -> Result<Option<Resolution>> {
+        let (resolution, scale) = match (settings.resolution, settings.scale) {
+            (Some(resolution), Some(scale)) => (Some(resolution), Some(scale)),
+            (resolution, scale) => {
+                let current = monitor.get_display_settings()?;
+                (resolution.or(current.resolution), scale.or(current.scale))
+            }
+        };
+        Ok(resolution.map(|resolution| resolution.oriented(settings.orientation).scaled(scale)))
+    }
+
+    /// Gives the monitors mirroring another one its position and orientation,
+    /// every monitor of a group then uses the leader's resolution or else the biggest one they all support
+    fn resolve_mirrors(settings: &mut [(&Monitor, DisplaySettings)]) -> Result<()> {
+        let mut leaders: Vec<MonitorName> = settings
+            .iter()
+            .filter(|(_, settings)| settings.enabled)
+            .filter_map(|(_, settings)| settings.mirror.clone())
+            .collect();
+        leaders.sort();
+        leaders.dedup();
+
+        for leader in leaders {
+            let leader_settings = settings
+                .iter()
+                .find(|(monitor, settings)| monitor.name == leader && settings.enabled)
+                .map(|(_, settings)| settings.clone())
+                .ok_or_else(|| {
+                    ThisError::InvalidMirror(format!("{} is mirrored but not turned on", leader))
+                })?;
+            if let Some(mirror) = &leader_settings.mirror {
+                Err(ThisError::InvalidMirror(format!(
+                    "{} is mirrored while mirroring {} itself",
+                    leader, mirror
+                )))?;
+            }
+
+            let group: Vec<&Monitor> = settings
+                .iter()
+                .filter(|(monitor, settings)| {
+                    monitor.name == leader
+                        || settings.enabled && settings.mirror.as_ref() == Some(&leader)
+                })
+                .map(|(monitor, _)| *monitor)
+                .collect();
+
+            let resolution = match leader_settings.resolution {
+                Some(resolution) => resolution,
+                None => {
+                    let mut common: Option<BTreeSet<Resolution>> = None;
+                    for monitor in &group {
+                        let resolutions = monitor
+                            .get_modes()?
+                            .into_iter()
+                            .map(|mode| mode.resolution)
+                            .collect();
+                        common = Some(match common {
+                            Some(common) => common.intersection(&resolutions).copied().collect(),
+                            None => resolutions,
+                        });
+                    }
+
+                    common
+                        .and_then(|common| common.last().copied())
+                        .ok_or_else(|| {
+                            ThisError::NoCommonResolution(
+                                group.iter().map(|monitor| monitor.name.clone()).collect(),
+                            )
+                        })?
+                }
+            };
+
+            for (monitor, settings) in settings.iter_mut() {
+                if !group.contains(monitor) {
+                    continue;
+                }
+
+                // Keeps the refresh rate when available at the shared resolution, takes the fastest one otherwise
+                let refresh_rates: Vec<u32> = monitor
+                    .get_modes()?
+                    .into_iter()
+                    .filter(|mode| mode.resolution == resolution)
+                    .map(|mode| mode.refresh_rate)
+                    .collect();
+                if settings
+                    .refresh_rate
+                    .is_none_or(|refresh_rate| !refresh_rates.contains(&refresh_rate))
+                {
+                    settings.refresh_rate = refresh_rates.into_iter().max();
+                }
+
+                settings.resolution = Some(resolution);
+                if monitor.name != leader {
+                    settings.position = leader_settings.position;
+                    settings.orientation = leader_settings.orientation;
+                    settings.scale = leader_settings.scale;
+                    settings.primary = false;
+                }
+            }
         }
 
         Ok(())
     }
 }
+
+/// Empty spans between the spans, as start and end coordinates
+fn gaps(mut spans: Vec<(i32, i32)>) -> Vec<(i32, i32)> {
+    spans.sort();
+
+    let mut gaps = Vec::new();
+    let mut covered_until: Option<i32> = None;
+    for (start, end) in spans {
+        if let Some(covered_until) = covered_until
+            && start > covered_until
+        {
+            gaps.push((covered_until, start));
+        }
+        covered_until = Some(covered_until.map_or(end, |covered_until| covered_until.max(end)));
+    }
+    gaps
+}
+
+/// Total length of the gaps before a coordinate
+fn gap_before(gaps: &[(i32, i32)], coordinate: i32) -> i32 {
+    gaps.iter()
+        .filter(|(_, end)| *end <= coordinate)
+        .map(|(start, end)| end - start)
+        .sum()
+}
+
+/// Position of a monitor of the given size once placed next to the reference one
+fn placed(
+    placement: &Placement,
+    size: Resolution,
+    reference: Pos,
+    reference_size: Resolution,
+) -> Option<Pos> {
+    let (width, height) = (size.width as i32, size.height as i32);
+    let (reference_width, reference_height) =
+        (reference_size.width as i32, reference_size.height as i32);
+
+    let position = if placement.side.is_horizontal() {
+        let y = match placement.align {
+            None | Some(Align::Top) => reference.y,
+            Some(Align::Bottom) => reference.y + reference_height - height,
+            Some(Align::Center) => reference.y + (reference_height - height) / 2,
+            Some(Align::Left | Align::Right) => return None,
+        };
+        let x = match placement.side {
+            Side::RightOf(_) => reference.x + reference_width,
+            _ => reference.x - width,
+        };
+        Pos { x, y }
+    } else {
+        let x = match placement.align {
+            None | Some(Align::Left) => reference.x,
+            Some(Align::Right) => reference.x + reference_width - width,
+            Some(Align::Center) => reference.x + (reference_width - width) / 2,
+            Some(Align::Top | Align::Bottom) => return None,
+        };
+        let y = match placement.side {
+            Side::Below(_) => reference.y + reference_height,
+            _ => reference.y - height,
+        };
+        Pos { x, y }
+    };
+
+    Some(position)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        backend::{DisplayBackend, fake::FakeBackend},
+        display_settings::DisplayOrientation,
+    };
+    use std::sync::Arc;
+
+    fn settings(x: i32, y: i32, width: u32, height: u32) -> DisplaySettings {
+        DisplaySettings {
+            position: Pos { x, y },
+            placement: None,
+            orientation: DisplayOrientation::Landscape,
+            resolution: Some(Resolution { width, height }),
+            refresh_rate: Some(60),
+            scale: None,
+            flipped: false,
+            primary: x == 0 && y == 0,
+            enabled: true,
+            mirror: None,
+        }
+    }
+
+    /// A laptop screen with a bigger monitor on its right
+    fn backend() -> Arc<FakeBackend> {
+        Arc::new(FakeBackend::new([
+            (String::from("LAPTOP"), settings(0, 0, 1920, 1080)),
+            (String::from("DESK"), settings(1920, 0, 2560, 1440)),
+        ]))
+    }
+
+    fn monitors(backend: &Arc<FakeBackend>) -> Vec<Monitor> {
+        let backend: Arc<dyn DisplayBackend> = backend.clone();
+        Monitor::get_monitors(&backend).unwrap()
+    }
+
+    fn preset(map: impl IntoIterator<Item = (&'static str, DisplaySettings)>) -> Preset {
+        Preset {
+            name: String::from("Test"),
+            map: map
+                .into_iter()
+                .map(|(name, settings)| (name.to_string(), settings))
+                .collect(),
+            ..Preset::default()
+        }
+    }
+
+    fn current(monitors: &[Monitor], name: &str) -> DisplaySettings {
+        monitors
+            .iter()
+            .find(|monitor| monitor.name == name)
+            .unwrap()
+            .get_display_settings()
+            .unwrap()
+    }
+
+    #[test]
+    fn captures_current_config() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = Preset::from_current_config(&monitors).unwrap();
+
+        assert_eq!(
+            preset.layout(),
+            "DESK: 1920/0,Landscape,2560x1440@60\nLAPTOP: 0/0,Landscape,1920x1080@60,primary"
+        );
+    }
+
+    #[test]
+    fn captures_mirrors() {
+        let backend = Arc::new(FakeBackend::new([
+            (String::from("LAPTOP"), settings(0, 0, 1920, 1080)),
+            (String::from("PROJECTOR"), settings(0, 0, 1920, 1080)),
+        ]));
+        let monitors = monitors(&backend);
+        let preset = Preset::from_current_config(&monitors).unwrap();
+
+        assert_eq!(preset.map["PROJECTOR"].mirror.as_deref(), Some("LAPTOP"));
+        assert!(!preset.map["PROJECTOR"].primary);
+    }
+
+    #[test]
+    fn applies_only_changed_monitors() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(1920, 0, 1920, 1080)),
+        ]);
+
+        let report = preset.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::Applied);
+        let applied = backend.applied();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].0, "DESK");
+        assert_eq!(
+            current(&monitors, "DESK").resolution,
+            Some(Resolution {
+                width: 1920,
+                height: 1080
+            })
+        );
+
+        let report = preset.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::AlreadyApplied);
+        assert_eq!(backend.applied().len(), 1);
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn applies_only_changed_monitors_when_the_layout_moves() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(0, -1440, 2560, 1440)),
+        ]);
+        assert!(!preset.is_applied(&monitors).unwrap());
+
+        preset.apply(&monitors).unwrap();
+        let applied = backend.applied();
+        assert_eq!(applied.len(), 1);
+        assert_eq!(applied[0].0, "DESK");
+        assert_eq!(current(&monitors, "DESK").position, Pos { x: 0, y: -1440 });
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn compares_layouts_where_the_backend_puts_them() {
+        let backend = Arc::new(
+            FakeBackend::new([
+                (String::from("LAPTOP"), settings(0, 0, 1920, 1080)),
+                (String::from("DESK"), settings(1920, 0, 2560, 1440)),
+            ])
+            .with_origin(Origin::TopLeft),
+        );
+        let monitors = monitors(&backend);
+        // The primary monitor is not the top left one, the backend moves the layout by 0/1440
+        let mut preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(0, -1440, 2560, 1440)),
+        ]);
+
+        preset.apply(&monitors).unwrap();
+        assert_eq!(backend.applied().len(), 2);
+        assert_eq!(current(&monitors, "LAPTOP").position, Pos { x: 0, y: 1440 });
+        assert_eq!(current(&monitors, "DESK").position, Pos { x: 0, y: 0 });
+        assert!(preset.is_applied(&monitors).unwrap());
+        let report = preset.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::AlreadyApplied);
+
+        preset.map.get_mut("DESK").unwrap().refresh_rate = Some(144);
+        assert!(!preset.is_applied(&monitors).unwrap());
+        preset.apply(&monitors).unwrap();
+        let applied = backend.applied();
+        assert_eq!(applied.len(), 3);
+        assert_eq!(applied[2].0, "DESK");
+        assert_eq!(applied[2].1.position, Pos { x: 0, y: 0 });
+        assert_eq!(current(&monitors, "LAPTOP").position, Pos { x: 0, y: 1440 });
+    }
+
+    #[test]
+    fn rolls_back_when_the_backend_fails() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let before = Preset::from_current_config(&monitors).unwrap().layout();
+        let preset = preset([
+            ("LAPTOP", settings(2560, 0, 1920, 1080)),
+            ("DESK", settings(0, 0, 2560, 1440)),
+        ]);
+
+        backend.fail_next_apply();
+        let err = preset.apply(&monitors).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Failed to change the display settings of LAPTOP: simulated failure, the previous display settings were restored"
+        );
+        assert_eq!(
+            Preset::from_current_config(&monitors).unwrap().layout(),
+            before
+        );
+    }
+
+    #[test]
+    fn refuses_unsupported_modes_before_applying() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset([("DESK", settings(1920, 0, 1024, 768))]);
+
+        assert!(preset.apply(&monitors).is_err());
+        assert!(backend.applied().is_empty());
+    }
+
+    #[test]
+    fn dry_runs_show_the_targets_sent_to_the_backend() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(0, -1440, 2560, 1440)),
+        ]);
+
+        assert_eq!(
+            preset.dry_run(&monitors).unwrap().to_string(),
+            "DESK: 1920/0,Landscape,2560x1440@60 -> 0/-1440,Landscape,2560x1440@60\n\
+             LAPTOP: 0/0,Landscape,1920x1080@60,primary (unchanged)\n\
+             The preset can be applied"
+        );
+    }
+
+    #[test]
+    fn dry_runs_state_how_far_the_backend_moves_the_layout() {
+        let laptop = DisplaySettings {
+            primary: true,
+            ..settings(0, 1440, 1920, 1080)
+        };
+        let desk = DisplaySettings {
+            primary: false,
+            ..settings(0, 0, 2560, 1440)
+        };
+        let backend = Arc::new(
+            FakeBackend::new([
+                (String::from("LAPTOP"), laptop),
+                (String::from("DESK"), desk),
+            ])
+            .with_origin(Origin::TopLeft),
+        );
+        let monitors = monitors(&backend);
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(0, -1440, 2560, 1440)),
+        ]);
+
+        assert_eq!(
+            preset.dry_run(&monitors).unwrap().to_string(),
+            "Positions as the backend applies them, the layout moved by 0/1440 for its top left corner to sit at 0,0\n\
+             DESK: 0/0,Landscape,2560x1440@60 (unchanged)\n\
+             LAPTOP: 0/1440,Landscape,1920x1080@60,primary (unchanged)\n\
+             The preset is already applied"
+        );
+    }
+
+    #[test]
+    fn resolves_placements() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let mut desk = settings(0, 0, 2560, 1440);
+        desk.primary = false;
+        desk.placement = Some(Placement {
+            side: Side::RightOf(String::from("LAPTOP")),
+            align: Some(Align::Center),
+        });
+        let preset = preset([("LAPTOP", settings(0, 0, 1920, 1080)), ("DESK", desk)]);
+
+        let resolved = preset.resolve(&monitors).unwrap();
+        let desk = resolved
+            .iter()
+            .find(|(monitor, _)| monitor.name == "DESK")
+            .unwrap();
+        assert_eq!(desk.1.position, Pos { x: 1920, y: -180 });
+        assert_eq!(desk.1.placement, None);
+    }
+
+    #[test]
+    fn places_scaled_monitors_by_the_area_they_cover() {
+        let laptop = DisplaySettings {
+            scale: Some(2.),
+            ..settings(0, 0, 2880, 1800)
+        };
+        let backend = Arc::new(FakeBackend::new([
+            (String::from("LAPTOP"), laptop),
+            (String::from("DESK"), settings(1440, 0, 2560, 1440)),
+        ]));
+        let monitors = monitors(&backend);
+        let mut desk = settings(0, 0, 2560, 1440);
+        desk.primary = false;
+        desk.placement = Some(Placement {
+            side: Side::RightOf(String::from("LAPTOP")),
+            align: None,
+        });
+        // The laptop keeps its current scale, covering 1440x900
+        let mut laptop = settings(0, 0, 2880, 1800);
+        laptop.scale = None;
+        let preset = preset([("LAPTOP", laptop), ("DESK", desk)]);
+
+        let resolved = preset.resolve(&monitors).unwrap();
+        let desk = resolved
+            .iter()
+            .find(|(monitor, _)| monitor.name == "DESK")
+            .unwrap();
+        assert_eq!(desk.1.position, Pos { x: 1440, y: 0 });
+        assert!(preset.check(&monitors).unwrap().is_empty());
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn refuses_placements_going_round_in_circles() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let placed = |reference: &str| {
+            let mut settings = settings(0, 0, 1920, 1080);
+            settings.placement = Some(Placement {
+                side: Side::Below(reference.to_string()),
+                align: None,
+            });
+            settings
+        };
+        let preset = preset([("LAPTOP", placed("DESK")), ("DESK", placed("LAPTOP"))]);
+
+        let err = preset.resolve(&monitors).unwrap_err();
+        assert!(
+            err.to_string().contains("going round in circles"),
+            "{}",
+            err
+        );
+    }
+
+    #[test]
+    fn checks_and_normalizes_layouts() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let mut preset = preset([
+            ("LAPTOP", settings(0, 0, 1920, 1080)),
+            ("DESK", settings(1900, 5, 2560, 1440)),
+        ]);
+
+        assert_eq!(
+            preset.check(&monitors).unwrap(),
+            vec![Diagnostic::Overlap {
+                monitors: (String::from("DESK"), String::from("LAPTOP")),
+                size: Resolution {
+                    width: 20,
+                    height: 1075,
+                },
+            }]
+        );
+
+        assert!(preset.normalize(&monitors).unwrap());
+        assert_eq!(preset.map["DESK"].position, Pos { x: 1920, y: 0 });
+        assert!(preset.check(&monitors).unwrap().is_empty());
+        assert!(!preset.normalize(&monitors).unwrap());
+    }
+
+    /// A projector between the laptop and the desk monitor, which isn't connected
+    fn preset_with_a_missing_projector(on_missing_monitor: MissingMonitorPolicy) -> Preset {
+        Preset {
+            on_missing_monitor,
+            ..preset([
+                ("LAPTOP", settings(0, 0, 1920, 1080)),
+                ("PROJECTOR", settings(1920, 0, 1920, 1080)),
+                ("DESK", settings(3840, 0, 2560, 1440)),
+            ])
+        }
+    }
+
+    #[test]
+    fn refuses_missing_monitors_when_strict() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset_with_a_missing_projector(MissingMonitorPolicy::Strict);
+
+        assert!(preset.apply(&monitors).is_err());
+        assert!(backend.applied().is_empty());
+    }
+
+    #[test]
+    fn skips_missing_monitors() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let preset = preset_with_a_missing_projector(MissingMonitorPolicy::ApplyAvailable);
+
+        let report = preset.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::Applied);
+        assert_eq!(report.skipped, ["PROJECTOR"]);
+        assert_eq!(
+            report.diagnostics,
+            [Diagnostic::Unreachable(String::from("DESK"))]
+        );
+        assert_eq!(current(&monitors, "DESK").position, Pos { x: 3840, y: 0 });
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn closes_the_gaps_left_by_missing_monitors() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let relayout = preset_with_a_missing_projector(MissingMonitorPolicy::Relayout);
+
+        let report = relayout.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::AlreadyApplied);
+        assert_eq!(report.skipped, ["PROJECTOR"]);
+        assert!(report.diagnostics.is_empty());
+        assert_eq!(current(&monitors, "DESK").position, Pos { x: 1920, y: 0 });
+
+        // The top left monitor takes over from a missing primary one
+        let without_primary = Preset {
+            on_missing_monitor: MissingMonitorPolicy::Relayout,
+            ..preset([
+                ("PROJECTOR", settings(0, 0, 1920, 1080)),
+                ("DESK", settings(1920, 0, 2560, 1440)),
+                ("LAPTOP", settings(4480, 0, 1920, 1080)),
+            ])
+        };
+        let report = without_primary.apply(&monitors).unwrap();
+        assert_eq!(report.skipped, ["PROJECTOR"]);
+        assert!(report.diagnostics.is_empty());
+        let desk = current(&monitors, "DESK");
+        assert_eq!(desk.position, Pos { x: 0, y: 0 });
+        assert!(desk.primary);
+        assert_eq!(current(&monitors, "LAPTOP").position, Pos { x: 2560, y: 0 });
+    }
+
+    #[test]
+    fn applies_whole_mirror_groups() {
+        let backend = Arc::new(FakeBackend::new([
+            (String::from("LAPTOP"), settings(0, 0, 2560, 1440)),
+            (String::from("PROJECTOR"), settings(2560, 0, 1920, 1080)),
+            (String::from("DESK"), settings(-1920, 0, 1920, 1080)),
+        ]));
+        let monitors = monitors(&backend);
+        let mut projector = settings(2560, 0, 1920, 1080);
+        projector.mirror = Some(String::from("LAPTOP"));
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 2560, 1440)),
+            ("PROJECTOR", projector),
+            ("DESK", settings(-1920, 0, 1920, 1080)),
+        ]);
+
+        // Only the projector changes, its leader is applied along with it
+        preset.apply(&monitors).unwrap();
+        let mut applied: Vec<MonitorName> = backend
+            .applied()
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect();
+        applied.sort();
+        assert_eq!(applied, ["LAPTOP", "PROJECTOR"]);
+        let projector = current(&monitors, "PROJECTOR");
+        assert_eq!(projector.position, Pos { x: 0, y: 0 });
+        assert_eq!(
+            projector.resolution,
+            Some(Resolution {
+                width: 2560,
+                height: 1440
+            })
+        );
+        assert!(!projector.primary);
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn mirrors_at_a_resolution_every_monitor_supports() {
+        // Each has a mode of its own on top of the ones they share
+        let backend = Arc::new(FakeBackend::new([
+            (String::from("LAPTOP"), settings(0, 0, 1920, 1200)),
+            (String::from("PROJECTOR"), settings(1920, 0, 1024, 768)),
+        ]));
+        let monitors = monitors(&backend);
+        let mut laptop = settings(0, 0, 0, 0);
+        laptop.resolution = None;
+        laptop.refresh_rate = Some(144);
+        let mut projector = settings(0, 0, 0, 0);
+        projector.mirror = Some(String::from("LAPTOP"));
+        let preset = preset([("LAPTOP", laptop), ("PROJECTOR", projector)]);
+
+        preset.apply(&monitors).unwrap();
+        for name in ["LAPTOP", "PROJECTOR"] {
+            let settings = current(&monitors, name);
+            assert_eq!(
+                settings.resolution,
+                Some(Resolution {
+                    width: 3840,
+                    height: 2160
+                })
+            );
+            // The only refresh rate at that resolution
+            assert_eq!(settings.refresh_rate, Some(60));
+        }
+    }
+
+    #[test]
+    fn turns_monitors_off_and_back_on_when_rolled_back() {
+        let backend = backend();
+        let monitors = monitors(&backend);
+        let before = Preset::from_current_config(&monitors).unwrap().layout();
+        // Turned off monitors keep a position overlapping the laptop
+        let preset = preset([
+            ("LAPTOP", settings(0, 0, 1280, 720)),
+            ("DESK", DisplaySettings::disabled()),
+        ]);
+        assert!(preset.check(&monitors).unwrap().is_empty());
+
+        backend.fail_next_apply();
+        assert!(preset.apply(&monitors).is_err());
+        let desk = current(&monitors, "DESK");
+        assert!(desk.enabled);
+        assert_eq!(desk.position, Pos { x: 1920, y: 0 });
+        assert_eq!(
+            Preset::from_current_config(&monitors).unwrap().layout(),
+            before
+        );
+
+        let report = preset.apply(&monitors).unwrap();
+        assert_eq!(report.outcome, ApplyOutcome::Applied);
+        assert!(report.diagnostics.is_empty());
+        assert!(!current(&monitors, "DESK").enabled);
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+
+    #[test]
+    fn migrates_presets_to_edid_names() {
+        let backend = Arc::new(FakeBackend::default());
+        let monitors = monitors(&backend);
+        assert_eq!(
+            monitors
+                .iter()
+                .map(|monitor| monitor.name.as_str())
+                .collect::<Vec<_>>(),
+            ["DEL A0FE 1279347248", "GSM 5B7F 237797"]
+        );
+
+        let mut desk = settings(1920, 0, 2560, 1440);
+        desk.refresh_rate = Some(144);
+        let mut preset = preset([
+            ("\\\\.\\DISPLAY1\\Monitor0", settings(0, 0, 1920, 1080)),
+            ("\\\\.\\DISPLAY2\\Monitor0", desk),
+        ]);
+        assert!(preset.migrate_names(&monitors));
+        assert!(preset.map.contains_key("DEL A0FE 1279347248"));
+        assert!(preset.map.contains_key("GSM 5B7F 237797"));
+        assert!(preset.is_applied(&monitors).unwrap());
+    }
+}
@@ -0,0 +1,330 @@
+use crate::{
+    error::ThisError,
+    preset::{Keybind, Preset},
+};
+use std::{
+    fmt::Display,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+/// What importing does with a preset named like an existing one
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OnConflict {
+    /// Imports it under the first free name of `Desk (2)`, `Desk (3)`...
+    Rename,
+    /// Replaces the settings of the existing one with the imported ones
+    Replace,
+    /// Keeps the existing one
+    Skip,
+}
+
+impl FromStr for OnConflict {
+    type Err = ThisError;
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "rename" => Ok(Self::Rename),
+            "replace" => Ok(Self::Replace),
+            "skip" => Ok(Self::Skip),
+            _ => Err(ThisError::InvalidArgument {
+                value: s.to_string(),
+                expected: "rename, replace or skip",
+            }),
+        }
+    }
+}
+
+/// An imported preset whose keyboard shortcut was removed, another preset using it already
+#[derive(Debug, Clone, PartialEq)]
+pub struct KeybindClash {
+    pub preset: String,
+    pub keybind: Keybind,
+    pub used_by: String,
+}
+
+/// What [`import`] did
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct ImportReport {
+    /// Names of the presets added or replaced, once renamed
+    pub imported: Vec<String>,
+    /// Previous and new names of the presets renamed, see [`OnConflict::Rename`]
+    pub renamed: Vec<(String, String)>,
+    pub replaced: Vec<String>,
+    pub skipped: Vec<String>,
+    pub keybind_clashes: Vec<KeybindClash>,
+}
+
+impl Display for ImportReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.imported.is_empty() {
+            f.write_str("No preset imported")?;
+        } else {
+            write!(f, "Imported {}", self.imported.join(", "))?;
+        }
+        for (name, new_name) in &self.renamed {
+            write!(f, "\n{} renamed to {}", name, new_name)?;
+        }
+        for name in &self.replaced {
+            write!(f, "\n{} replaced", name)?;
+        }
+        for name in &self.skipped {
+            write!(f, "\n{} skipped, a preset has that name already", name)?;
+        }
+        for clash in &self.keybind_clashes {
+            write!(
+                f,
+                "\n⚠ Shortcut {} of {} removed, {} uses it already",
+                clash.keybind, clash.preset, clash.used_by
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Names of the imported presets that some of the presets have already
+pub fn conflicts(presets: &[Arc<Mutex<Preset>>], imported: &[Preset]) -> Vec<String> {
+    imported
+        .iter()
+        .filter(|imported| {
+            presets
+                .iter()
+                .any(|preset| preset.lock().unwrap().name == imported.name)
+        })
+        .map(|imported| imported.name.clone())
+        .collect()
+}
+
+/// Adds the imported presets to the presets, removing the keyboard shortcuts other presets use already
+pub fn import(
+    presets: &mut Vec<Arc<Mutex<Preset>>>,
+    imported: Vec<Preset>,
+    on_conflict: OnConflict,
+) -> ImportReport {
+    let mut report = ImportReport::default();
+    // Presets yet to be replaced don't keep their shortcut from the imported ones
+    let mut pending_replacements = match on_conflict {
+        OnConflict::Replace => conflicts(presets, &imported),
+        OnConflict::Rename | OnConflict::Skip => Vec::new(),
+    };
+
+    for mut preset in imported {
+        let mut replaced = None;
+        if let Some(existing) = presets
+            .iter()
+            .position(|other| other.lock().unwrap().name == preset.name)
+        {
+            match on_conflict {
+                OnConflict::Rename => {
+                    let new_name = free_name(presets, &preset.name);
+                    let name = std::mem::replace(&mut preset.name, new_name.clone());
+                    report.renamed.push((name, new_name));
+                }
+                OnConflict::Replace => replaced = Some(existing),
+                OnConflict::Skip => {
+                    report.skipped.push(preset.name);
+                    continue;
+                }
+            }
+        }
+
+        if let Some(keybind) = preset.keybind.clone()
+            && let Some(used_by) = presets.iter().find_map(|other| {
+                let other = other.lock().unwrap();
+                (!pending_replacements.contains(&other.name)
+                    && other.keybind.as_ref() == Some(&keybind))
+                .then(|| other.name.clone())
+            })
+        {
+            report.keybind_clashes.push(KeybindClash {
+                preset: preset.name.clone(),
+                keybind,
+                used_by,
+            });
+            preset.keybind = None;
+        }
+
+        report.imported.push(preset.name.clone());
+        match replaced {
+            // In place, for the menus and the background process holding it
+            Some(index) => {
+                pending_replacements.retain(|name| *name != preset.name);
+                report.replaced.push(preset.name.clone());
+                *presets[index].lock().unwrap() = preset;
+            }
+            None => presets.push(Arc::new(Mutex::new(preset))),
+        }
+    }
+    report
+}
+
+/// First of `name (2)`, `name (3)`... not taken by a preset
+fn free_name(presets: &[Arc<Mutex<Preset>>], name: &str) -> String {
+    (2..)
+        .map(|index| format!("{} ({})", name, index))
+        .find(|name| {
+            !presets
+                .iter()
+                .any(|preset| preset.lock().unwrap().name == *name)
+        })
+        .unwrap()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn preset(name: &str, description: &str, keybind: Option<&str>) -> Preset {
+        let mut preset = Preset::default();
+        preset.name = String::from(name);
+        preset.description = Some(String::from(description));
+        preset.keybind = keybind.map(|keybind| Keybind::try_from(keybind).unwrap());
+        preset
+    }
+
+    fn presets(presets: impl IntoIterator<Item = Preset>) -> Vec<Arc<Mutex<Preset>>> {
+        presets
+            .into_iter()
+            .map(|preset| Arc::new(Mutex::new(preset)))
+            .collect()
+    }
+
+    /// Name, description and shortcut of each preset
+    fn contents(presets: &[Arc<Mutex<Preset>>]) -> Vec<(String, String, Option<String>)> {
+        presets
+            .iter()
+            .map(|preset| {
+                let preset = preset.lock().unwrap();
+                (
+                    preset.name.clone(),
+                    preset.description.clone().unwrap(),
+                    preset.keybind.as_ref().map(ToString::to_string),
+                )
+            })
+            .collect()
+    }
+
+    fn content(
+        name: &str,
+        description: &str,
+        keybind: Option<&str>,
+    ) -> (String, String, Option<String>) {
+        (
+            String::from(name),
+            String::from(description),
+            keybind.map(String::from),
+        )
+    }
+
+    #[test]
+    fn finds_conflicting_names() {
+        let existing = presets([preset("Desk", "", None), preset("Sofa", "", None)]);
+        let imported = [preset("Sofa", "", None), preset("Travel", "", None)];
+
+        assert_eq!(conflicts(&existing, &imported), ["Sofa"]);
+    }
+
+    #[test]
+    fn renames_to_the_first_free_name() {
+        let mut existing = presets([preset("Desk", "old", None), preset("Desk (2)", "old", None)]);
+        let imported = vec![
+            preset("Desk", "new", None),
+            // Matched against the one just imported
+            preset("Sofa", "first", None),
+            preset("Sofa", "second", None),
+        ];
+
+        let report = import(&mut existing, imported, OnConflict::Rename);
+        assert_eq!(report.imported, ["Desk (3)", "Sofa", "Sofa (2)"]);
+        assert_eq!(
+            report.renamed,
+            [
+                (String::from("Desk"), String::from("Desk (3)")),
+                (String::from("Sofa"), String::from("Sofa (2)")),
+            ]
+        );
+        assert_eq!(
+            contents(&existing),
+            [
+                content("Desk", "old", None),
+                content("Desk (2)", "old", None),
+                content("Desk (3)", "new", None),
+                content("Sofa", "first", None),
+                content("Sofa (2)", "second", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn replaces_in_place_freeing_the_replaced_shortcuts() {
+        let mut existing = presets([
+            preset("Desk", "old", Some("CTRL+F1")),
+            preset("Sofa", "old", None),
+        ]);
+        let kept = existing[0].clone();
+        // Takes the shortcut of the Desk preset, replaced by one without it
+        let imported = vec![
+            preset("Travel", "new", Some("CTRL+F1")),
+            preset("Desk", "new", None),
+            preset("Desk", "newer", None),
+        ];
+
+        let report = import(&mut existing, imported, OnConflict::Replace);
+        assert_eq!(report.imported, ["Travel", "Desk", "Desk"]);
+        assert_eq!(report.replaced, ["Desk", "Desk"]);
+        assert!(report.keybind_clashes.is_empty());
+        assert_eq!(
+            contents(&existing),
+            [
+                content("Desk", "newer", None),
+                content("Sofa", "old", None),
+                content("Travel", "new", Some("CONTROL+F1")),
+            ]
+        );
+        assert!(Arc::ptr_eq(&kept, &existing[0]));
+    }
+
+    #[test]
+    fn skips_taken_names() {
+        let mut existing = presets([preset("Desk", "old", None)]);
+        let imported = vec![
+            preset("Desk", "new", None),
+            preset("Sofa", "first", None),
+            preset("Sofa", "second", None),
+        ];
+
+        let report = import(&mut existing, imported, OnConflict::Skip);
+        assert_eq!(report.imported, ["Sofa"]);
+        assert_eq!(report.skipped, ["Desk", "Sofa"]);
+        assert_eq!(
+            contents(&existing),
+            [content("Desk", "old", None), content("Sofa", "first", None)]
+        );
+    }
+
+    #[test]
+    fn removes_shortcuts_used_already() {
+        let mut existing = presets([preset("Desk", "old", Some("CTRL+F1"))]);
+        let imported = vec![
+            preset("Sofa", "new", Some("ctrl+f1")),
+            preset("Travel", "new", Some("CTRL+F2")),
+        ];
+
+        let report = import(&mut existing, imported, OnConflict::Rename);
+        assert_eq!(
+            report.keybind_clashes,
+            [KeybindClash {
+                preset: String::from("Sofa"),
+                keybind: Keybind::try_from("CTRL+F1").unwrap(),
+                used_by: String::from("Desk"),
+            }]
+        );
+        assert_eq!(
+            contents(&existing),
+            [
+                content("Desk", "old", Some("CONTROL+F1")),
+                content("Sofa", "new", None),
+                content("Travel", "new", Some("CONTROL+F2")),
+            ]
+        );
+    }
+}